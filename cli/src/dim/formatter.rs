@@ -35,24 +35,33 @@ impl UnitFormatter {
     ) -> Result<JsonValue, DimError> {
         // Determine preferred unit using precedence:
         // 1. Query parameter override
-        // 2. Block-type preference in config
-        // 3. Dimension-level preference in config
-        // 4. Schema defaultUnit
-        // 5. Base SI unit (no conversion)
+        // 2. Block-type preference on the block's nodes (nearest scope first)
+        // 3. Dimension-level preference on the block's nodes
+        // 4. Block-type preference in config
+        // 5. Dimension-level preference in config
+        // 6. Schema defaultUnit
+        // 7. Base SI unit (no conversion)
+
+        // Schema dimension, falling back to config.toml [dimensions]
+        let dimension = property_metadata
+            .and_then(|meta| meta.dimension.as_ref())
+            .or_else(|| unit_preferences.property_dimensions.get(property_name));
 
         let preferred_unit = unit_preferences
             .query_overrides
             .get(property_name)
             .or_else(|| {
                 block_type
-                    .and_then(|bt| unit_preferences.block_types.get(bt))
+                    .and_then(|bt| unit_preferences.scoped_block_types.get(bt))
                     .and_then(|props| props.get(property_name))
             })
+            .or_else(|| dimension.and_then(|dim| unit_preferences.scoped_dimensions.get(dim)))
             .or_else(|| {
-                property_metadata
-                    .and_then(|meta| meta.dimension.as_ref())
-                    .and_then(|dim| unit_preferences.dimensions.get(dim))
+                block_type
+                    .and_then(|bt| unit_preferences.block_types.get(bt))
+                    .and_then(|props| props.get(property_name))
             })
+            .or_else(|| dimension.and_then(|dim| unit_preferences.dimensions.get(dim)))
             .or_else(|| property_metadata.and_then(|meta| meta.default_unit.as_ref()));
 
         // If no preferred unit, return value as-is
@@ -90,10 +99,8 @@ impl UnitFormatter {
                     None
                 }
             } else {
-                // Try to infer from property metadata
-                property_metadata
-                    .and_then(|meta| meta.dimension.as_ref())
-                    .and_then(|dim| get_reference_unit_for_dimension(dim))
+                // Try to infer from the property's dimension
+                dimension.and_then(|dim| get_reference_unit_for_dimension(dim))
             };
 
         let base_unit = match base_unit {
@@ -132,8 +139,40 @@ pub struct UnitPreferences {
     pub block_types: HashMap<String, HashMap<String, String>>, // block_type -> {property -> unit}
     /// Dimension-level defaults from config
     pub dimensions: HashMap<String, String>, // dimension -> unit
+    /// Declared property dimensions from config.toml [dimensions]
+    pub property_dimensions: HashMap<String, String>, // property -> dimension
     /// Original unit strings (from _property_original keys)
     pub original_strings: HashMap<String, String>, // _property_original -> original string
+    /// Block-type preferences from `unitPreferences` on the block's nodes
+    pub scoped_block_types: HashMap<String, HashMap<String, String>>,
+    /// Dimension-level preferences from `unitPreferences` on the block's nodes
+    pub scoped_dimensions: HashMap<String, String>,
+}
+
+impl UnitPreferences {
+    /// These preferences with a node-scoped `unitPreferences` table (as resolved by
+    /// `ScopeResolver::resolve_unit_preferences`) ranked above the config ones
+    pub fn with_scoped(&self, scoped: &toml::Table) -> Self {
+        let mut preferences = self.clone();
+        for (name, entries) in scoped {
+            let Some(entries) = entries.as_table() else {
+                continue;
+            };
+            let units = entries
+                .iter()
+                .filter_map(|(key, unit)| unit.as_str().map(|u| (key.clone(), u.to_string())));
+            if name == "dimensions" {
+                preferences.scoped_dimensions.extend(units);
+            } else {
+                preferences
+                    .scoped_block_types
+                    .entry(name.clone())
+                    .or_default()
+                    .extend(units);
+            }
+        }
+        preferences
+    }
 }
 
 /// Get reference unit for a dimension (for conversion)
@@ -6,9 +6,20 @@ use crate::schema::registry::PropertyMetadata;
 use std::collections::HashMap;
 use toml::{map::Map, Value};
 
+/// A unit string whose dimension contradicts the one its property is declared with
+#[derive(Debug, Clone, PartialEq)]
+pub struct DimensionMismatch {
+    pub property: String,
+    /// The unit string as written
+    pub value: String,
+    pub expected: String,
+    pub reason: String,
+}
+
 /// Process TOML values to parse unit strings and normalize them
 pub struct UnitProcessor {
     parser: Option<DimParser>,
+    mismatches: Vec<DimensionMismatch>,
 }
 
 impl UnitProcessor {
@@ -16,7 +27,15 @@ impl UnitProcessor {
     /// If WASM file is not available, unit parsing will be skipped
     pub fn new() -> Self {
         let parser = DimParser::new().ok();
-        Self { parser }
+        Self {
+            parser,
+            mismatches: Vec::new(),
+        }
+    }
+
+    /// Dimension mismatches found since the last call; the values are kept as written
+    pub fn take_mismatches(&mut self) -> Vec<DimensionMismatch> {
+        std::mem::take(&mut self.mismatches)
     }
 }
 
@@ -138,10 +157,12 @@ impl UnitProcessor {
                                         &parse_result,
                                         expected_dimension,
                                     ) {
-                                        eprintln!(
-                                            "Warning: Dimension validation failed for property '{}': {}",
-                                            key, e
-                                        );
+                                        self.mismatches.push(DimensionMismatch {
+                                            property: key.clone(),
+                                            value: s.clone(),
+                                            expected: expected_dimension.clone(),
+                                            reason: e.to_string(),
+                                        });
                                         // Keep original string on validation failure
                                         processed.insert(key.clone(), value.clone());
                                         continue;
@@ -201,6 +222,34 @@ impl UnitProcessor {
 
         Ok(processed)
     }
+
+    /// Process a HashMap using declared property dimensions (config.toml [dimensions])
+    /// Declared properties are parsed and validated as if their schema gave the dimension
+    pub fn process_hashmap_with_dimensions(
+        &mut self,
+        extra: &HashMap<String, Value>,
+        dimensions: &HashMap<String, String>,
+    ) -> Result<HashMap<String, Value>, DimError> {
+        self.process_hashmap_with_schema(extra, &dimension_metadata(dimensions))
+    }
+}
+
+/// Property metadata carrying only the dimensions declared in config.toml [dimensions]
+pub fn dimension_metadata(
+    dimensions: &HashMap<String, String>,
+) -> HashMap<String, PropertyMetadata> {
+    dimensions
+        .iter()
+        .map(|(property, dimension)| {
+            (
+                property.clone(),
+                PropertyMetadata {
+                    dimension: Some(dimension.clone()),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -265,4 +314,27 @@ mod tests {
             panic!("Expected Table");
         }
     }
+
+    #[test]
+    fn test_declared_dimensions_become_metadata() {
+        let mut dimensions = HashMap::new();
+        dimensions.insert("ambientTemperature".to_string(), "temperature".to_string());
+
+        let metadata = dimension_metadata(&dimensions);
+        let meta = metadata.get("ambientTemperature").unwrap();
+        assert_eq!(meta.dimension.as_deref(), Some("temperature"));
+        assert!(meta.default_unit.is_none());
+
+        // Declared properties that aren't unit strings are kept as-is
+        let mut extra = HashMap::new();
+        extra.insert("ambientTemperature".to_string(), Value::Float(293.15));
+        let mut processor = UnitProcessor::new();
+        let processed = processor
+            .process_hashmap_with_dimensions(&extra, &dimensions)
+            .unwrap();
+        assert_eq!(
+            processed.get("ambientTemperature"),
+            Some(&Value::Float(293.15))
+        );
+    }
 }
@@ -52,6 +52,15 @@ impl UnitProcessor {
         Ok(extra.clone())
     }
 
+    pub fn process_hashmap_with_dimensions(
+        &mut self,
+        extra: &HashMap<String, toml::Value>,
+        _dimensions: &HashMap<String, String>,
+    ) -> Result<HashMap<String, toml::Value>, DimError> {
+        // In WASM builds, just return the hashmap as-is
+        Ok(extra.clone())
+    }
+
     pub fn process_hashmap_with_schema(
         &mut self,
         extra: &HashMap<String, toml::Value>,
@@ -87,7 +96,16 @@ pub struct UnitPreferences {
     pub query_overrides: HashMap<String, String>,
     pub block_types: HashMap<String, HashMap<String, String>>,
     pub dimensions: HashMap<String, String>,
+    pub property_dimensions: HashMap<String, String>,
     pub original_strings: HashMap<String, String>,
+    pub scoped_block_types: HashMap<String, HashMap<String, String>>,
+    pub scoped_dimensions: HashMap<String, String>,
+}
+
+impl UnitPreferences {
+    pub fn with_scoped(&self, _scoped: &toml::Table) -> Self {
+        self.clone()
+    }
 }
 
 pub fn validate_dimension(
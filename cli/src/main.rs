@@ -1,19 +1,105 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 #[cfg(not(target_arch = "wasm32"))]
 use dagger::dim;
 use dagger::parser;
 use dagger::query;
 use dagger::schema;
 use dagger::scope;
+use serde::Serialize;
+
+/// Exit code for a command that ran to completion (warnings allowed)
+const EXIT_OK: i32 = 0;
+/// Exit code when validation reported at least one error
+const EXIT_VALIDATION_FAILED: i32 = 1;
+/// Exit code when the command itself failed (unreadable network, bad query, ...)
+const EXIT_COMMAND_FAILED: i32 = 2;
 
 #[derive(Parser)]
 #[command(name = "dagger")]
 #[command(about = "Network configuration parser and query tool", long_about = None)]
+#[command(after_help = "Exit codes:
+  0  command completed (warnings may have been reported)
+  1  validation reported one or more errors
+  2  command failed (invalid arguments, unreadable network, query error)
+
+With --format json or ndjson, results are written to stdout as JSON and
+loader diagnostics are written to stderr as one JSON object per line.")]
 struct Cli {
+    /// Output format for results and diagnostics
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// A single pretty-printed JSON document
+    Json,
+    /// One compact JSON object per line
+    Ndjson,
+}
+
+/// A single diagnostic as emitted in JSON output
+#[derive(Serialize)]
+struct Diagnostic<'a> {
+    code: &'a str,
+    severity: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    property: Option<&'a str>,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    constraint: Option<&'a str>,
+}
+
+impl<'a> Diagnostic<'a> {
+    fn from_load_issue(issue: &'a parser::validation::ValidationIssue) -> Self {
+        Self {
+            code: issue.code,
+            severity: match issue.severity {
+                parser::validation::IssueSeverity::Error => "error",
+                parser::validation::IssueSeverity::Warning => "warning",
+            },
+            location: issue.location.clone(),
+            property: None,
+            message: &issue.message,
+            value: None,
+            constraint: None,
+        }
+    }
+
+    fn from_schema_issue(issue: &'a schema::validator::ValidationIssue, location: String) -> Self {
+        Self {
+            code: issue.code,
+            severity: match issue.severity {
+                schema::validator::IssueSeverity::Error => "error",
+                schema::validator::IssueSeverity::Warning => "warning",
+            },
+            location: Some(location),
+            property: issue.property.as_deref(),
+            message: &issue.message,
+            value: issue.value.as_ref(),
+            constraint: issue.constraint.as_deref(),
+        }
+    }
+}
+
+/// Exit code for a finished validation: any error-severity diagnostic fails it
+fn validation_exit_code(diagnostics: &[Diagnostic]) -> i32 {
+    if diagnostics.iter().any(|d| d.severity == "error") {
+        EXIT_VALIDATION_FAILED
+    } else {
+        EXIT_OK
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Export network as JSON
@@ -25,6 +111,23 @@ enum Commands {
         /// Output file (default: stdout)
         #[arg(short, long)]
         output: Option<String>,
+
+        /// Materialize every inherited property into each block, tagged with its scope
+        #[arg(long)]
+        resolved: bool,
+
+        /// Fill in schema defaults for properties blocks don't set or inherit
+        #[arg(long)]
+        schema_defaults: bool,
+
+        /// Parse block properties with this schema version's dimensions
+        /// (default: schemaVersion from config.toml)
+        #[arg(long)]
+        schema_version: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from config.toml, else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
     },
 
     /// List all nodes in the network
@@ -32,6 +135,15 @@ enum Commands {
         /// Network directory path
         #[arg(default_value = "../network/preset1")]
         path: String,
+
+        /// Parse block properties with this schema version's dimensions
+        /// (default: schemaVersion from config.toml)
+        #[arg(long)]
+        schema_version: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from config.toml, else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
     },
 
     /// Query a specific path in the network
@@ -42,6 +154,23 @@ enum Commands {
         /// Network directory path
         #[arg(default_value = "../network/preset1")]
         path: String,
+
+        /// Render the result as a table instead of JSON
+        #[arg(long, value_parser = ["csv", "md", "toml", "yaml", "table"])]
+        output: Option<String>,
+
+        /// Fill in schema defaults for properties blocks don't set or inherit
+        #[arg(long)]
+        schema_defaults: bool,
+
+        /// Parse block properties with this schema version's dimensions
+        /// (default: schemaVersion from config.toml)
+        #[arg(long)]
+        schema_version: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from config.toml, else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
     },
 
     /// Resolve a property value using scope inheritance
@@ -60,151 +189,685 @@ enum Commands {
         path: String,
     },
 
+    /// Explain how a block property resolves through the scope chain
+    Explain {
+        /// Block property path (e.g., "branch-4/blocks/0/ambientTemperature"),
+        /// optionally with explicit scopes ("...?scope=block,global")
+        target: String,
+
+        /// Network directory path
+        #[arg(default_value = "../network/preset1")]
+        path: String,
+    },
+
     /// Validate blocks against schema libraries
     Validate {
-        /// Schema version to use (e.g., "v1.0")
+        /// Schema version to use (e.g., "v1.0"; default: schemaVersion from config.toml)
+        version: Option<String>,
+
+        /// Network directory path (default: ../network/preset1)
+        path: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from config.toml, else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
+
+        /// Also report where each required property's value comes from
+        #[arg(long, short)]
+        verbose: bool,
+    },
+
+    /// List known properties with their dimension, scopes and block types
+    Properties {
+        /// Network directory path
+        #[arg(default_value = "../network/preset1")]
+        path: String,
+
+        /// Only properties used by this block type
+        #[arg(long)]
+        block_type: Option<String>,
+
+        /// Schema version to read property metadata from (default: schemaVersion from
+        /// config.toml, else every version found)
+        #[arg(long)]
+        schema_version: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from config.toml, else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
+    },
+
+    /// Rewrite a network's TOML files from one schema version to another
+    Migrate {
+        /// Network directory path
+        #[arg(default_value = "../network/preset1")]
+        path: String,
+
+        /// Version the network uses now (default: schemaVersion from config.toml)
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Version to migrate to
+        #[arg(long)]
+        to: String,
+
+        /// Schemas directory path (default: schemasDir from config.toml, else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
+
+        /// Report the changes without writing any file
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Browse the schema libraries in a schemas directory
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommands,
+    },
+
+    /// Inspect a network's config.toml
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum SchemaCommands {
+    /// List the versions found, or the block types of one version
+    List {
+        /// Schema version to list block types for
+        version: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from the network's config.toml,
+        /// else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
+
+        /// Network directory whose config.toml pins the schemas
+        #[arg(long, default_value = "../network/preset1")]
+        network: String,
+    },
+
+    /// Print the flattened schema and property metadata of a version's block types
+    Show {
+        /// Schema version (e.g., "v1.0")
         version: String,
 
+        /// Only this block type
+        block_type: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from the network's config.toml,
+        /// else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
+
+        /// Network directory whose config.toml pins the schemas
+        #[arg(long, default_value = "../network/preset1")]
+        network: String,
+    },
+
+    /// Generate a JSON Schema per node type for validating network TOML in editors
+    ExportJsonschema {
+        /// Schema version (default: schemaVersion from the network's config.toml)
+        version: Option<String>,
+
+        /// Write `<node type>.schema.json` files here instead of printing them
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from the network's config.toml,
+        /// else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
+
+        /// Network directory whose config.toml pins the schemas
+        #[arg(long, default_value = "../network/preset1")]
+        network: String,
+    },
+
+    /// Draft schemas for the block types used in existing networks
+    Infer {
+        /// Network directory paths
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// Version to record in the drafts (e.g., "v1.0")
+        #[arg(long)]
+        schema_version: String,
+
+        /// Write one `<block-type>.json` file per block type here instead of printing them
+        #[arg(short, long)]
+        output_dir: Option<String>,
+
+        /// Overwrite schema files that already exist in the output directory
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check config.toml for unknown scopes, unused rules and mismatched units
+    Validate {
+        /// Network directory path
+        #[arg(default_value = "../network/preset1")]
+        path: String,
+
+        /// Schema version to check block types against (default: schemaVersion from
+        /// config.toml, else every version found)
+        #[arg(long)]
+        schema_version: Option<String>,
+
+        /// Schemas directory path (default: schemasDir from config.toml, else ../schemas)
+        #[arg(long)]
+        schemas_dir: Option<String>,
+    },
+
+    /// Print configuration entries and the file each one came from
+    Show {
         /// Network directory path
         #[arg(default_value = "../network/preset1")]
         path: String,
 
-        /// Schemas directory path
-        #[arg(long, default_value = "../schemas")]
-        schemas_dir: String,
+        /// Merge the user-level file and the extends chain under config.toml
+        #[arg(long)]
+        effective: bool,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
+    let format = cli.format;
 
-    match cli.command {
-        Commands::Export { path, output } => match export_network(&path, output.as_deref()) {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
-        Commands::List { path } => match list_nodes(&path) {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
-        Commands::Query { query, path } => match query_network(&path, &query) {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
+    let result = match cli.command {
+        Commands::Export {
+            path,
+            output,
+            resolved,
+            schema_defaults,
+            schema_version,
+            schemas_dir,
+        } => export_network(
+            &path,
+            output.as_deref(),
+            resolved,
+            schema_defaults,
+            schema_version.as_deref(),
+            schemas_dir.as_deref(),
+            format,
+        ),
+        Commands::List {
+            path,
+            schema_version,
+            schemas_dir,
+        } => list_nodes(
+            &path,
+            schema_version.as_deref(),
+            schemas_dir.as_deref(),
+            format,
+        ),
+        Commands::Query {
+            query,
+            path,
+            output,
+            schema_defaults,
+            schema_version,
+            schemas_dir,
+        } => query_network(
+            &path,
+            &query,
+            output.as_deref(),
+            schema_defaults,
+            schema_version.as_deref(),
+            schemas_dir.as_deref(),
+            format,
+        ),
         Commands::Resolve {
             node_id,
             block_index,
             property,
             path,
-        } => match resolve_property(&path, &node_id, block_index, &property) {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        },
+        } => resolve_property(&path, &node_id, block_index, &property, format),
+        Commands::Explain { target, path } => explain_property(&path, &target, format),
         Commands::Validate {
             version,
             path,
             schemas_dir,
-        } => match validate_network(&path, &version, &schemas_dir) {
-            Ok(_) => {}
-            Err(e) => {
+            verbose,
+        } => {
+            // A lone positional argument naming a directory is the network path
+            let (version, path) = match (version, path) {
+                (Some(version), None) if std::path::Path::new(&version).is_dir() => (None, version),
+                (version, path) => (
+                    version,
+                    path.unwrap_or_else(|| "../network/preset1".to_string()),
+                ),
+            };
+            validate_network(
+                &path,
+                version.as_deref(),
+                schemas_dir.as_deref(),
+                verbose,
+                format,
+            )
+        }
+        Commands::Properties {
+            path,
+            block_type,
+            schema_version,
+            schemas_dir,
+        } => list_properties(
+            &path,
+            block_type.as_deref(),
+            schema_version.as_deref(),
+            schemas_dir.as_deref(),
+            format,
+        ),
+        Commands::Migrate {
+            path,
+            from,
+            to,
+            schemas_dir,
+            dry_run,
+        } => migrate_network(
+            &path,
+            from.as_deref(),
+            &to,
+            schemas_dir.as_deref(),
+            dry_run,
+            format,
+        ),
+        Commands::Schema {
+            command:
+                SchemaCommands::List {
+                    version,
+                    schemas_dir,
+                    network,
+                },
+        } => list_schemas(&network, schemas_dir.as_deref(), version.as_deref(), format),
+        Commands::Schema {
+            command:
+                SchemaCommands::Show {
+                    version,
+                    block_type,
+                    schemas_dir,
+                    network,
+                },
+        } => show_schemas(
+            &network,
+            schemas_dir.as_deref(),
+            &version,
+            block_type.as_deref(),
+            format,
+        ),
+        Commands::Schema {
+            command:
+                SchemaCommands::ExportJsonschema {
+                    version,
+                    output_dir,
+                    schemas_dir,
+                    network,
+                },
+        } => export_json_schemas(
+            &network,
+            schemas_dir.as_deref(),
+            version.as_deref(),
+            output_dir.as_deref(),
+            format,
+        ),
+        Commands::Schema {
+            command:
+                SchemaCommands::Infer {
+                    paths,
+                    schema_version,
+                    output_dir,
+                    force,
+                },
+        } => infer_schemas(
+            &paths,
+            &schema_version,
+            output_dir.as_deref(),
+            force,
+            format,
+        ),
+        Commands::Config {
+            command:
+                ConfigCommands::Validate {
+                    path,
+                    schema_version,
+                    schemas_dir,
+                },
+        } => validate_config(
+            &path,
+            schema_version.as_deref(),
+            schemas_dir.as_deref(),
+            format,
+        ),
+        Commands::Config {
+            command: ConfigCommands::Show { path, effective },
+        } => show_config(&path, effective, format),
+    };
+
+    match result {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            if format == OutputFormat::Text {
                 eprintln!("Error: {}", e);
-                std::process::exit(1);
+            } else {
+                let message = e.to_string();
+                let diagnostic = Diagnostic {
+                    code: "command-failed",
+                    severity: "error",
+                    location: None,
+                    property: None,
+                    message: &message,
+                    value: None,
+                    constraint: None,
+                };
+                eprintln!(
+                    "{}",
+                    serde_json::to_string(&diagnostic).unwrap_or(message.clone())
+                );
             }
-        },
+            std::process::exit(EXIT_COMMAND_FAILED);
+        }
     }
 }
 
-fn export_network(path: &str, output: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let (network, validation) = parser::load_network_from_directory(path)?;
+/// Print loader diagnostics to stderr in the requested format
+fn report_load_diagnostics(
+    validation: &parser::validation::ValidationResult,
+    format: OutputFormat,
+) {
+    if !validation.has_issues() {
+        return;
+    }
+
+    match format {
+        OutputFormat::Text => eprintln!("{}", validation),
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            for issue in validation.issues() {
+                if let Ok(line) = serde_json::to_string(&Diagnostic::from_load_issue(issue)) {
+                    eprintln!("{}", line);
+                }
+            }
+        }
+    }
+}
+
+/// Print a JSON value to stdout in the requested format
+fn print_json(
+    value: &serde_json::Value,
+    format: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        OutputFormat::Ndjson => println!("{}", serde_json::to_string(value)?),
+        OutputFormat::Text | OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(value)?)
+        }
+    }
+    Ok(())
+}
+
+/// Print rows as left-aligned columns under a header
+fn print_table<const N: usize>(header: [&str; N], rows: Vec<[String; N]>) {
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(header.map(String::from)).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Load the effective config for a network directory: its config.toml layered over
+/// the files it extends (empty if none exist, or if they can't be read; the loader
+/// reports that as a `config-invalid` diagnostic)
+fn load_network_config(path: &str) -> Result<scope::config::Config, Box<dyn std::error::Error>> {
+    let config_path = std::path::Path::new(path).join("config.toml");
+    let Ok(layered) = scope::layers::LayeredConfig::load(&config_path) else {
+        return Ok(scope::config::Config::empty());
+    };
+
+    let mut config = match layered.config() {
+        Ok(config) => config,
+        Err(e) => {
+            // Deserialization errors for untagged rules are opaque; prefer located ones
+            let mut validation = parser::validation::ValidationResult::new();
+            for layer in &layered.layers {
+                let content = std::fs::read_to_string(&layer.path)?;
+                let result = scope::validator::ConfigValidator::new()
+                    .validate(&content, &layer.path.display().to_string());
+                validation.errors.extend(result.errors);
+                validation.warnings.extend(result.warnings);
+            }
+            if validation.is_valid() {
+                return Err(e);
+            }
+            return Err(format!("Invalid config.toml\n{}", validation).into());
+        }
+    };
+
+    // Normalize unit strings in [properties], validating declared [dimensions]
+    let mut processor = dim::processor::UnitProcessor::new();
+    config.properties =
+        processor.process_hashmap_with_dimensions(&config.properties, &config.dimensions)?;
+
+    Ok(config)
+}
+
+/// Unit preferences for display: the network's config over the `[unitPreferences]`
+/// of the user-level file. A user file that can't be read is reported and skipped
+fn load_unit_preferences(
+    path: &str,
+    config: &scope::config::Config,
+    format: OutputFormat,
+) -> scope::config::UnitPreferences {
+    let config_path = std::path::Path::new(path).join("config.toml");
+    let preferences = scope::layers::LayeredConfig::load_with_user_preferences(&config_path)
+        .and_then(|layered| {
+            let preferences = layered.merged.get("unitPreferences").cloned();
+            Ok(preferences.map(|p| p.try_into()).transpose()?)
+        });
+    match preferences {
+        Ok(Some(preferences)) => preferences,
+        Ok(None) => config.unit_preferences.clone(),
+        // Without the user file it loads, so the user file is at fault
+        Err(e) if scope::layers::LayeredConfig::load(&config_path).is_ok() => {
+            let mut validation = parser::validation::ValidationResult::new();
+            validation.add_warning(
+                "user-config-invalid",
+                format!("Ignoring user-level unit preferences: {}", e),
+                scope::layers::user_config_path().map(|p| p.display().to_string()),
+            );
+            report_load_diagnostics(&validation, format);
+            config.unit_preferences.clone()
+        }
+        Err(_) => config.unit_preferences.clone(),
+    }
+}
+
+fn export_network(
+    path: &str,
+    output: Option<&str>,
+    resolved: bool,
+    schema_defaults: bool,
+    schema_version: Option<&str>,
+    schemas_dir: Option<&str>,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let (mut network, validation, schemas) =
+        load_network(path, schema_version, schemas_dir, format)?;
+
+    report_load_diagnostics(&validation, format);
 
-    // Print validation issues if any
-    if validation.has_issues() {
-        eprintln!("{}", validation);
+    let resolver = scope::resolver::ScopeResolver::new(load_network_config(path)?);
+    if resolved {
+        // With a schema, properties it declares are resolved even where no block sets them
+        network = match &schemas {
+            Some((registry, version)) => {
+                network.materialize_with_schema(&resolver, registry, version)?
+            }
+            None => network.materialize(&resolver)?,
+        };
+    }
+    // After materializing, so inherited values keep their own scope
+    if schema_defaults {
+        apply_schema_defaults(&mut network, &resolver, schemas.as_ref())?;
     }
 
-    let json = serde_json::to_string_pretty(&network)?;
+    let json = match format {
+        OutputFormat::Ndjson => serde_json::to_string(&network)?,
+        OutputFormat::Text | OutputFormat::Json => serde_json::to_string_pretty(&network)?,
+    };
 
     if let Some(output_path) = output {
         std::fs::write(output_path, json)?;
-        println!("Network exported to {}", output_path);
+        if format == OutputFormat::Text {
+            println!("Network exported to {}", output_path);
+        }
     } else {
         println!("{}", json);
     }
 
-    Ok(())
+    Ok(EXIT_OK)
 }
 
-fn list_nodes(path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let (network, validation) = parser::load_network_from_directory(path)?;
+fn list_nodes(
+    path: &str,
+    schema_version: Option<&str>,
+    schemas_dir: Option<&str>,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let (network, validation, _) = load_network(path, schema_version, schemas_dir, format)?;
 
-    // Print validation issues if any
-    if validation.has_issues() {
-        eprintln!("{}", validation);
-    }
+    report_load_diagnostics(&validation, format);
 
-    println!("Network: {} ({})", network.label, network.id);
-    println!("\nNodes ({}):", network.nodes.len());
-    for node in &network.nodes {
-        let base = node.base();
-        println!(
-            "  - {} ({}) at ({}, {})",
-            base.id,
-            base.label_display(),
-            base.position.x,
-            base.position.y
-        );
-    }
+    let nodes: Vec<serde_json::Value> = network
+        .nodes
+        .iter()
+        .map(|node| {
+            let base = node.base();
+            serde_json::json!({
+                "id": base.id,
+                "type": base.type_,
+                "label": base.label_display(),
+                "position": base.position,
+                "parentId": base.parent_id,
+            })
+        })
+        .collect();
+    let edges: Vec<serde_json::Value> = network
+        .edges
+        .iter()
+        .map(|edge| {
+            serde_json::json!({
+                "id": edge.id,
+                "source": edge.source,
+                "target": edge.target,
+                "weight": edge.data.weight,
+            })
+        })
+        .collect();
 
-    println!("\nEdges ({}):", network.edges.len());
-    for edge in &network.edges {
-        println!(
-            "  - {} -> {} (weight: {})",
-            edge.source, edge.target, edge.data.weight
-        );
+    match format {
+        OutputFormat::Text => {
+            println!("Network: {} ({})", network.label, network.id);
+            println!("\nNodes ({}):", network.nodes.len());
+            for node in &network.nodes {
+                let base = node.base();
+                println!(
+                    "  - {} ({}) at ({}, {})",
+                    base.id,
+                    base.label_display(),
+                    base.position.x,
+                    base.position.y
+                );
+            }
+
+            println!("\nEdges ({}):", network.edges.len());
+            for edge in &network.edges {
+                println!(
+                    "  - {} -> {} (weight: {})",
+                    edge.source, edge.target, edge.data.weight
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let document = serde_json::json!({
+                "network": { "id": network.id, "label": network.label },
+                "nodes": nodes,
+                "edges": edges,
+            });
+            print_json(&document, format)?;
+        }
+        OutputFormat::Ndjson => {
+            println!(
+                "{}",
+                serde_json::json!({ "kind": "network", "id": network.id, "label": network.label })
+            );
+            for mut node in nodes {
+                node["kind"] = "node".into();
+                println!("{}", node);
+            }
+            for mut edge in edges {
+                edge["kind"] = "edge".into();
+                println!("{}", edge);
+            }
+        }
     }
 
-    Ok(())
+    Ok(EXIT_OK)
 }
 
-fn query_network(path: &str, query_str: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let (network, validation) = parser::load_network_from_directory(path)?;
+fn query_network(
+    path: &str,
+    query_str: &str,
+    output: Option<&str>,
+    schema_defaults: bool,
+    schema_version: Option<&str>,
+    schemas_dir: Option<&str>,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let (mut network, validation, schemas) =
+        load_network(path, schema_version, schemas_dir, format)?;
+    let (schema_registry, schema_version) = match &schemas {
+        Some((registry, version)) => (Some(registry), Some(version.as_str())),
+        None => (None, None),
+    };
 
-    // Print validation issues if any
-    if validation.has_issues() {
-        eprintln!("{}", validation);
-    }
+    report_load_diagnostics(&validation, format);
 
     // Parse the query path and extract unit overrides
     let (query_path, unit_overrides) = query::parser::parse_query_path_with_params(query_str)
         .map_err(|e| format!("Failed to parse query: {}", e))?;
 
     // Load config for scope resolution and unit preferences
-    let config_path = std::path::Path::new(path).join("config.toml");
-    let config = if config_path.exists() {
-        scope::config::Config::load_from_file(&config_path)?
-    } else {
-        scope::config::Config::empty()
-    };
+    let config = load_network_config(path)?;
     let resolver = scope::resolver::ScopeResolver::new(config.clone());
+    if schema_defaults {
+        apply_schema_defaults(&mut network, &resolver, schemas.as_ref())?;
+    }
 
-    // Build unit preferences from config and query overrides
+    // Build unit preferences from config (over the user-level file) and query overrides
+    let preferences = load_unit_preferences(path, &config, format);
     let unit_preferences = dim::formatter::UnitPreferences {
         query_overrides: unit_overrides.properties,
-        block_types: config.unit_preferences.block_types.clone(),
-        dimensions: config.unit_preferences.dimensions.clone(),
+        block_types: preferences.block_types,
+        dimensions: preferences.dimensions,
+        property_dimensions: config.dimensions.clone(),
         original_strings: std::collections::HashMap::new(), // Will be populated during query execution
+        ..Default::default()
     };
 
     // Create executor with scope resolver and unit preferences
@@ -212,18 +875,37 @@ fn query_network(path: &str, query_str: &str) -> Result<(), Box<dyn std::error::
         &network,
         Some(&resolver),
         unit_preferences,
-        None, // Schema registry not available in CLI for now
-        None, // Schema version not available in CLI for now
+        schema_registry,
+        schema_version,
     );
     let result = executor
         .execute(&query_path)
         .map_err(|e| format!("Query error: {}", e))?;
 
+    // Tabular output takes precedence over --format
+    if let Some(output) = output {
+        let table_format: query::formatter::TableFormat = output.parse()?;
+        println!(
+            "{}",
+            query::formatter::format_query_result_as(&result, table_format)?
+        );
+        return Ok(EXIT_OK);
+    }
+
     // Format and print the result
-    let formatted = query::formatter::format_query_result(&result);
-    println!("{}", formatted);
+    match (format, &result) {
+        (OutputFormat::Ndjson, serde_json::Value::Array(items)) => {
+            for item in items {
+                println!("{}", serde_json::to_string(item)?);
+            }
+        }
+        (OutputFormat::Ndjson, _) => print_json(&result, format)?,
+        (OutputFormat::Text | OutputFormat::Json, _) => {
+            println!("{}", query::formatter::format_query_result(&result));
+        }
+    }
 
-    Ok(())
+    Ok(EXIT_OK)
 }
 
 fn resolve_property(
@@ -231,18 +913,13 @@ fn resolve_property(
     node_id: &str,
     block_index: usize,
     property: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let (network, _validation) = parser::load_network_from_directory(path)?;
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let (network, validation) = parser::load_network_from_directory(path)?;
 
-    // Load config
-    let config_path = std::path::Path::new(path).join("config.toml");
-    let config = if config_path.exists() {
-        scope::config::Config::load_from_file(&config_path)?
-    } else {
-        scope::config::Config::empty()
-    };
+    report_load_diagnostics(&validation, format);
 
-    let resolver = scope::resolver::ScopeResolver::new(config);
+    let resolver = scope::resolver::ScopeResolver::new(load_network_config(path)?);
 
     // Find the node
     let branch_node = network
@@ -263,141 +940,1598 @@ fn resolve_property(
         )
     })?;
 
-    // Find the group if parent_id exists
-    let group = branch_node.base.parent_id.as_ref().and_then(|parent_id| {
-        network.nodes.iter().find_map(|n| match n {
-            parser::models::NodeData::Group(g) if g.base.id == *parent_id => Some(g),
-            _ => None,
-        })
-    });
+    let ctx = scope::resolver::BlockContext::in_network(&network, branch_node, block_index)?;
 
     // Resolve the property
-    let value = resolver.resolve_property(property, block, branch_node, group);
+    let trace = resolver.explain(property, &ctx)?;
+    let resolved = trace.value.clone().zip(trace.scope);
 
     // Get scope chain for display
-    let scope_chain = resolver.get_scope_chain_for_property(property, Some(&block.type_));
+    let scope_chain = trace.chain.clone();
+
+    // Whether each scope in the chain defines the property (None: scope not applicable)
+    let checked_scopes: Vec<(scope::config::ScopeLevel, Option<bool>)> = scope_chain
+        .iter()
+        .map(|scope| {
+            let steps: Vec<_> = trace.steps.iter().filter(|s| s.scope == *scope).collect();
+            let present = steps
+                .iter()
+                .any(|s| s.available)
+                .then(|| steps.iter().any(|s| s.value.is_some()));
+            (*scope, present)
+        })
+        .collect();
+
+    if format != OutputFormat::Text {
+        let (value, scope) = match &resolved {
+            Some((v, scope)) => (Some(query::executor::toml_to_json(v)), Some(*scope)),
+            None => (None, None),
+        };
+        let document = serde_json::json!({
+            "property": property,
+            "node": node_id,
+            "blockIndex": block_index,
+            "blockType": block.type_,
+            "scopeChain": scope_chain,
+            "found": resolved.is_some(),
+            "value": value,
+            "scope": scope,
+            "checkedScopes": checked_scopes
+                .iter()
+                .map(|(scope, present)| serde_json::json!({ "scope": scope, "present": present }))
+                .collect::<Vec<_>>(),
+        });
+        print_json(&document, format)?;
+        return Ok(EXIT_OK);
+    }
 
     println!("Property: {}", property);
     println!("Node: {}", node_id);
     println!("Block: {} (index {})", block.type_, block_index);
     println!("Scope chain: {:?}", scope_chain);
 
-    match value {
-        Some(v) => {
+    match resolved {
+        Some((v, _)) => {
             println!("Resolved value: {}", v);
             println!("\nJSON: {}", serde_json::to_string_pretty(&v)?);
         }
         None => {
             println!("Property not found in any scope");
             println!("\nChecked scopes:");
-            for scope in scope_chain {
-                match scope {
-                    scope::config::ScopeLevel::Block => {
-                        println!("  - Block: {}", block.extra.contains_key(property));
-                    }
-                    scope::config::ScopeLevel::Branch => {
-                        println!(
-                            "  - Branch: {}",
-                            branch_node.base.extra.contains_key(property)
-                        );
+            for (scope, present) in checked_scopes {
+                match (scope, present) {
+                    (scope::config::ScopeLevel::Group, None) => {
+                        println!("  - Group: (no parent)");
                     }
-                    scope::config::ScopeLevel::Group => {
-                        if let Some(g) = group {
-                            println!("  - Group: {}", g.base.extra.contains_key(property));
-                        } else {
-                            println!("  - Group: (no parent)");
-                        }
-                    }
-                    scope::config::ScopeLevel::Global => {
-                        println!("  - Global: {}", resolver.has_global_property(property));
+                    (scope, present) => {
+                        println!("  - {:?}: {}", scope, present.unwrap_or(false));
                     }
                 }
             }
         }
     }
 
-    Ok(())
+    Ok(EXIT_OK)
 }
 
-fn validate_network(
-    path: &str,
-    schema_version: &str,
-    schemas_dir: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let (network, _validation) = parser::load_network_from_directory(path)?;
+/// Block property addressed by an explain target
+struct ExplainTarget {
+    node_id: String,
+    block_index: usize,
+    property: String,
+    explicit_scopes: Vec<scope::config::ScopeLevel>,
+}
 
-    // Create schema registry and load the specified version
-    let schemas_path = std::path::PathBuf::from(schemas_dir);
-    let mut registry = schema::registry::SchemaRegistry::new(schemas_path);
+/// Parse an explain target such as "branch-4/blocks/0/ambientTemperature?scope=block,global"
+fn parse_explain_target(target: &str) -> Result<ExplainTarget, Box<dyn std::error::Error>> {
+    let usage = || {
+        format!(
+            "Cannot explain '{}': expected a block property path like branch-4/blocks/0/<property>",
+            target
+        )
+    };
 
-    match registry.load_library(schema_version) {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!(
-                "Warning: Failed to load schema library '{}': {}",
-                schema_version, e
-            );
-            eprintln!("Available versions: {:?}", registry.list_versions());
-            return Err(e);
+    let (property, scopes, inner) = match query::parser::parse_query_path(target)? {
+        query::parser::QueryPath::ScopeResolve {
+            property,
+            scopes,
+            inner,
+        } => {
+            let scopes = scopes
+                .iter()
+                .map(|s| s.parse::<scope::config::ScopeLevel>())
+                .collect::<Result<Vec<_>, _>>()?;
+            (property, scopes, *inner)
         }
+        query::parser::QueryPath::Property(property, inner) => (property, Vec::new(), *inner),
+        _ => return Err(usage().into()),
+    };
+
+    let query::parser::QueryPath::Index(block_index, blocks) = inner else {
+        return Err(usage().into());
+    };
+    let query::parser::QueryPath::Property(collection, node) = *blocks else {
+        return Err(usage().into());
+    };
+    let query::parser::QueryPath::Node(node_id) = *node else {
+        return Err(usage().into());
+    };
+    if collection != "blocks" {
+        return Err(usage().into());
     }
 
-    let validator = schema::validator::SchemaValidator::new(registry);
+    Ok(ExplainTarget {
+        node_id,
+        block_index,
+        property,
+        explicit_scopes: scopes,
+    })
+}
 
-    let mut total_issues = 0;
-    let mut total_errors = 0;
-    let mut total_warnings = 0;
+fn explain_property(
+    path: &str,
+    target: &str,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let ExplainTarget {
+        node_id,
+        block_index,
+        property,
+        explicit_scopes,
+    } = parse_explain_target(target)?;
 
-    // Validate all blocks in all branch nodes
-    for node in &network.nodes {
-        if let parser::models::NodeData::Branch(branch) = node {
-            for (idx, block) in branch.blocks.iter().enumerate() {
-                let result = validator.validate_block(block, schema_version);
+    let (network, validation) = parser::load_network_from_directory(path)?;
 
-                if result.has_issues() {
-                    println!(
-                        "\n{}[{}] (block type: {})",
-                        branch.base.id, idx, block.type_
-                    );
+    report_load_diagnostics(&validation, format);
 
-                    for issue in &result.issues {
-                        let prefix = match issue.severity {
-                            schema::validator::IssueSeverity::Error => {
-                                total_errors += 1;
-                                "ERROR"
-                            }
-                            schema::validator::IssueSeverity::Warning => {
-                                total_warnings += 1;
-                                "WARN"
-                            }
-                        };
+    let resolver = scope::resolver::ScopeResolver::new(load_network_config(path)?);
 
-                        if let Some(prop) = &issue.property {
-                            println!(
-                                "  [{}] {}: {} (property: {})",
-                                prefix, issue.message, prop, prop
-                            );
-                        } else {
-                            println!("  [{}] {}", prefix, issue.message);
-                        }
+    let branch_node = network
+        .nodes
+        .iter()
+        .find_map(|n| match n {
+            parser::models::NodeData::Branch(b) if b.base.id == node_id => Some(b),
+            _ => None,
+        })
+        .ok_or_else(|| format!("Node '{}' not found or is not a branch node", node_id))?;
+
+    if block_index >= branch_node.blocks.len() {
+        return Err(format!(
+            "Block index {} out of range ({} blocks)",
+            block_index,
+            branch_node.blocks.len()
+        )
+        .into());
+    }
+
+    let ctx = scope::resolver::BlockContext::in_network(&network, branch_node, block_index)?;
+
+    let trace = if explicit_scopes.is_empty() {
+        resolver.explain(&property, &ctx)?
+    } else {
+        resolver.explain_with_explicit_scopes(&property, &ctx, &explicit_scopes)?
+    };
+
+    if format != OutputFormat::Text {
+        let mut document = serde_json::to_value(&trace)?;
+        document["node"] = node_id.clone().into();
+        document["blockIndex"] = block_index.into();
+        print_json(&document, format)?;
+        return Ok(EXIT_OK);
+    }
+
+    let rule = match &trace.rule {
+        scope::resolver::ChainRule::General => "general inheritance".to_string(),
+        scope::resolver::ChainRule::Property => format!("rule for '{}'", property),
+        scope::resolver::ChainRule::BlockTypeOverride { block_type } => {
+            format!("override for '{}' on {}", property, block_type)
+        }
+        scope::resolver::ChainRule::Explicit => "explicit scopes".to_string(),
+    };
+
+    println!("Property: {}", property);
+    println!(
+        "Block: {}/blocks/{} ({})",
+        node_id, block_index, trace.block_type
+    );
+    println!(
+        "Chain: {} ({})",
+        trace
+            .chain
+            .iter()
+            .map(|s| s.as_str())
+            .collect::<Vec<_>>()
+            .join(" -> "),
+        rule
+    );
+    println!();
+    for step in &trace.steps {
+        let source = match (&step.scope, &step.node) {
+            (scope::config::ScopeLevel::Global, _) => "config.toml".to_string(),
+            (scope::config::ScopeLevel::BlockType, _) => {
+                format!("[blockDefaults.{}]", trace.block_type)
+            }
+            (scope::config::ScopeLevel::Upstream, _) if !step.sources.is_empty() => step
+                .sources
+                .iter()
+                .map(|s| format!("{}/blocks/{}", s.node, s.block_index))
+                .collect::<Vec<_>>()
+                .join(", "),
+            (_, Some(node)) => node.clone(),
+            (_, None) => "-".to_string(),
+        };
+        let value = match (&step.value, step.available) {
+            (_, false) => "(not applicable)".to_string(),
+            (Some(v), _) => v.to_string(),
+            (None, _) => "(not set)".to_string(),
+        };
+        let marker = if step.selected {
+            "  <- selected"
+        } else if step.merged {
+            "  <- merged"
+        } else {
+            ""
+        };
+        println!(
+            "  {:<8} {:<16} {}{}",
+            step.scope.as_str(),
+            source,
+            value,
+            marker
+        );
+    }
+    println!();
+
+    match (&trace.value, trace.scope) {
+        (Some(v), Some(scope)) => println!("Resolved: {} (from {})", v, scope.as_str()),
+        _ => println!("Property not found in any scope"),
+    }
+
+    if !trace.leaves.is_empty() {
+        println!("\nMerged fields:");
+        for leaf in &trace.leaves {
+            println!(
+                "  {:<24} {}{}",
+                leaf.path,
+                leaf.scope.as_str(),
+                leaf.node
+                    .as_ref()
+                    .map(|n| format!(" ({})", n))
+                    .unwrap_or_default()
+            );
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn validate_network(
+    path: &str,
+    schema_version: Option<&str>,
+    schemas_dir: Option<&str>,
+    verbose: bool,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let selection = schema_selection(path, schema_version, schemas_dir, format)?;
+    let schema_version = selection.required_version("No schema version given")?;
+
+    // Create schema registry and load the specified version
+    let schemas_path = std::path::PathBuf::from(&selection.schemas_dir);
+    let mut registry = schema::registry::SchemaRegistry::new(schemas_path);
+
+    // The hint is part of the error so JSON output carries it too
+    if let Err(e) = registry.load_library(schema_version) {
+        let available = registry.available_versions();
+        let hint = if available.is_empty() {
+            format!("no versions found in {}", selection.schemas_dir)
+        } else {
+            format!(
+                "available versions: {}",
+                available
+                    .iter()
+                    .map(|v| v.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        return Err(format!(
+            "Failed to load schema library '{}': {} ({})",
+            schema_version, e, hint
+        )
+        .into());
+    }
+
+    // Unit strings are parsed with the dimensions of the schema validated against
+    let (network, load_validation) =
+        parser::load_network_from_directory_with_schema(path, &registry, schema_version)?;
+
+    // Required properties may be inherited from branches, groups and config.toml
+    let resolver = scope::resolver::ScopeResolver::new(load_network_config(path)?);
+    let validator = schema::validator::SchemaValidator::new(registry).with_scope_resolver(resolver);
+
+    // Loader diagnostics are part of the validation report
+    let mut diagnostics: Vec<Diagnostic> = load_validation
+        .issues()
+        .map(Diagnostic::from_load_issue)
+        .collect();
+
+    // Validate all blocks in all branch nodes
+    let mut block_results = Vec::new();
+    for node in &network.nodes {
+        if let parser::models::NodeData::Branch(branch) = node {
+            for (idx, block) in branch.blocks.iter().enumerate() {
+                let ctx = scope::resolver::BlockContext::in_network(&network, branch, idx)?;
+                let result = validator.validate_block_in_context(&ctx, schema_version);
+                if result.has_issues() || (verbose && !result.satisfied.is_empty()) {
+                    block_results.push((branch, idx, block, result));
+                }
+            }
+        }
+    }
+
+    for (branch, idx, _, result) in &block_results {
+        for issue in &result.issues {
+            diagnostics.push(Diagnostic::from_schema_issue(
+                issue,
+                format!("{}/blocks/{}", branch.base.id, idx),
+            ));
+        }
+    }
+
+    let satisfied_entries: Vec<serde_json::Value> = block_results
+        .iter()
+        .flat_map(|(branch, idx, _, result)| {
+            result.satisfied.iter().map(move |satisfied| {
+                let mut entry = serde_json::json!({
+                    "location": format!("{}/blocks/{}", branch.base.id, idx),
+                });
+                if let (Some(entry), serde_json::Value::Object(fields)) =
+                    (entry.as_object_mut(), serde_json::json!(satisfied))
+                {
+                    entry.extend(fields);
+                }
+                entry
+            })
+        })
+        .collect();
+
+    let total_issues = diagnostics.len();
+    let total_errors = diagnostics.iter().filter(|d| d.severity == "error").count();
+    let total_warnings = total_issues - total_errors;
+
+    match format {
+        OutputFormat::Text => {
+            if load_validation.has_issues() {
+                eprintln!("{}", load_validation);
+            }
+
+            for (branch, idx, block, result) in &block_results {
+                println!(
+                    "\n{}[{}] (block type: {})",
+                    branch.base.id, idx, block.type_
+                );
+
+                for issue in &result.issues {
+                    let prefix = match issue.severity {
+                        schema::validator::IssueSeverity::Error => "ERROR",
+                        schema::validator::IssueSeverity::Warning => "WARN",
+                    };
+
+                    if let Some(prop) = &issue.property {
+                        println!(
+                            "  [{}] {}: {} (property: {})",
+                            prefix, issue.message, prop, prop
+                        );
+                    } else {
+                        println!("  [{}] {}", prefix, issue.message);
+                    }
+                }
+
+                if verbose {
+                    for satisfied in &result.satisfied {
+                        match &satisfied.node {
+                            Some(node) => println!(
+                                "  [OK] {} from {} '{}'",
+                                satisfied.property,
+                                satisfied.scope.as_str(),
+                                node
+                            ),
+                            None => println!(
+                                "  [OK] {} from {}",
+                                satisfied.property,
+                                satisfied.scope.as_str()
+                            ),
+                        }
+                    }
+                }
+            }
+
+            println!("\n=== Validation Summary ===");
+            println!("Schema version: {}", schema_version);
+            println!(
+                "Total issues: {} ({} errors, {} warnings)",
+                total_issues, total_errors, total_warnings
+            );
+        }
+        OutputFormat::Json => {
+            let mut document = serde_json::json!({
+                "schemaVersion": schema_version,
+                "issues": diagnostics,
+                "summary": {
+                    "issues": total_issues,
+                    "errors": total_errors,
+                    "warnings": total_warnings,
+                },
+            });
+            if verbose {
+                document["satisfied"] = serde_json::Value::Array(satisfied_entries);
+            }
+            print_json(&document, format)?;
+        }
+        OutputFormat::Ndjson => {
+            for diagnostic in &diagnostics {
+                println!("{}", serde_json::to_string(diagnostic)?);
+            }
+            if verbose {
+                for mut entry in satisfied_entries {
+                    entry["kind"] = serde_json::json!("satisfied");
+                    println!("{}", entry);
+                }
+            }
+            println!(
+                "{}",
+                serde_json::json!({
+                    "kind": "summary",
+                    "schemaVersion": schema_version,
+                    "issues": total_issues,
+                    "errors": total_errors,
+                    "warnings": total_warnings,
+                })
+            );
+        }
+    }
+
+    Ok(validation_exit_code(&diagnostics))
+}
+
+/// Schema version and directory chosen for a network
+struct SchemaSelection {
+    version: Option<String>,
+    schemas_dir: String,
+    /// The version came from config.toml rather than the command line
+    pinned: bool,
+    /// Why config.toml couldn't be read, when it couldn't
+    config_error: Option<String>,
+}
+
+impl SchemaSelection {
+    /// The selected version, or an error that says why config.toml didn't supply one
+    fn required_version(&self, missing: &str) -> Result<&str, String> {
+        self.version
+            .as_deref()
+            .ok_or_else(|| match &self.config_error {
+                Some(e) => format!("{}, and config.toml can't be read: {}", missing, e),
+                None => format!(
+                    "{}, and config.toml doesn't pin one with schemaVersion",
+                    missing
+                ),
+            })
+    }
+
+    /// Report an unreadable config.toml as a `config-invalid` diagnostic, for
+    /// commands that don't run the loader (which reports it already)
+    fn report_config_error(&self, path: &str, format: OutputFormat) {
+        if let Some(e) = &self.config_error {
+            let mut validation = parser::validation::ValidationResult::new();
+            validation.add_error(
+                "config-invalid",
+                e.clone(),
+                Some(
+                    std::path::Path::new(path)
+                        .join("config.toml")
+                        .display()
+                        .to_string(),
+                ),
+            );
+            report_load_diagnostics(&validation, format);
+        }
+    }
+}
+
+/// Pick the schema for a network: command-line flags first, then `schemaVersion`
+/// and `schemasDir` pinned in its config.toml. Warns when the requested version
+/// differs from the pinned one
+fn schema_selection(
+    path: &str,
+    schema_version: Option<&str>,
+    schemas_dir: Option<&str>,
+    format: OutputFormat,
+) -> Result<SchemaSelection, Box<dyn std::error::Error>> {
+    // A config.toml that can't be read pins nothing; the error is kept for the caller
+    let (pinned, config_error) = match parser::pinned_schema(path) {
+        Ok(pinned) => (pinned, None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    if let (Some(requested), Some(pinned)) = (schema_version, &pinned) {
+        if requested != pinned.version {
+            let mut validation = parser::validation::ValidationResult::new();
+            validation.add_warning(
+                "schema-version-mismatch",
+                format!(
+                    "Using schema version '{}', but config.toml pins '{}'",
+                    requested, pinned.version
+                ),
+                Some(
+                    std::path::Path::new(path)
+                        .join("config.toml")
+                        .display()
+                        .to_string(),
+                ),
+            );
+            report_load_diagnostics(&validation, format);
+        }
+    }
+
+    let schemas_dir = schemas_dir
+        .map(|dir| dir.to_string())
+        .or_else(|| {
+            pinned
+                .as_ref()
+                .and_then(|p| p.schemas_dir.as_ref())
+                .map(|dir| dir.display().to_string())
+        })
+        .unwrap_or_else(|| "../schemas".to_string());
+
+    Ok(SchemaSelection {
+        version: schema_version
+            .map(|v| v.to_string())
+            .or_else(|| pinned.as_ref().map(|p| p.version.clone())),
+        schemas_dir,
+        pinned: schema_version.is_none() && pinned.is_some(),
+        config_error,
+    })
+}
+
+/// A schema library and the version selected from it
+type LoadedSchema = (schema::registry::SchemaRegistry, String);
+
+/// Load a network, parsing it against a schema library when a version is given
+/// or pinned. A pinned library that can't be loaded is reported and skipped
+fn load_network(
+    path: &str,
+    schema_version: Option<&str>,
+    schemas_dir: Option<&str>,
+    format: OutputFormat,
+) -> Result<
+    (
+        parser::models::Network,
+        parser::validation::ValidationResult,
+        Option<LoadedSchema>,
+    ),
+    Box<dyn std::error::Error>,
+> {
+    let selection = schema_selection(path, schema_version, schemas_dir, format)?;
+
+    let mut unavailable = None;
+    let registry = match &selection.version {
+        Some(version) => {
+            match load_schema_libraries(&selection.schemas_dir, Some(version), format) {
+                Ok(registry) => Some((registry, version.clone())),
+                Err(e) if selection.pinned => {
+                    unavailable = Some(format!(
+                        "Pinned schema version '{}' could not be loaded: {}",
+                        version, e
+                    ));
+                    None
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        None => None,
+    };
+
+    let (network, mut validation) = match &registry {
+        Some((registry, version)) => {
+            parser::load_network_from_directory_with_schema(path, registry, version)?
+        }
+        None => parser::load_network_from_directory(path)?,
+    };
+
+    // The loader already reports a pinned library it couldn't find in a pinned schemasDir
+    if let Some(message) = unavailable {
+        if !validation
+            .warnings
+            .iter()
+            .any(|w| w.code == "schema-unavailable")
+        {
+            validation.add_warning(
+                "schema-unavailable",
+                message,
+                Some(
+                    std::path::Path::new(path)
+                        .join("config.toml")
+                        .display()
+                        .to_string(),
+                ),
+            );
+        }
+    }
+    Ok((network, validation, registry))
+}
+
+/// Fill in schema defaults (`--schema-defaults`), which needs a schema version
+fn apply_schema_defaults(
+    network: &mut parser::models::Network,
+    resolver: &scope::resolver::ScopeResolver,
+    schemas: Option<&LoadedSchema>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let (registry, version) = schemas.ok_or(
+        "--schema-defaults needs a schema version (--schema-version, or schemaVersion in config.toml)",
+    )?;
+    schema::defaults::apply_schema_defaults(network, resolver, registry, version)
+}
+
+/// Load one schema library, or every version found in the schemas directory
+fn load_schema_libraries(
+    schemas_dir: &str,
+    schema_version: Option<&str>,
+    format: OutputFormat,
+) -> Result<schema::registry::SchemaRegistry, Box<dyn std::error::Error>> {
+    let mut registry = schema::registry::SchemaRegistry::new(schemas_dir.into());
+    match schema_version {
+        Some(version) => registry.load_library(version)?,
+        None => {
+            for version in schema::registry::discover_versions(std::path::Path::new(schemas_dir)) {
+                if let Err(e) = registry.load_library(&version) {
+                    if format == OutputFormat::Text {
+                        eprintln!("Warning: Skipping schema library '{}': {}", version, e);
                     }
+                }
+            }
+        }
+    }
+    Ok(registry)
+}
+
+fn migrate_network(
+    path: &str,
+    from: Option<&str>,
+    to: &str,
+    schemas_dir: Option<&str>,
+    dry_run: bool,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let selection = schema_selection(path, from, schemas_dir, format)?;
+    selection.report_config_error(path, format);
+    let from = selection.required_version("No --from version given")?;
+
+    // Migrations are stored with the version they lead to, so load every version
+    let registry = load_schema_libraries(&selection.schemas_dir, None, format)?;
+    let steps = registry.migration_path(from, to)?;
+    let report = schema::migration::migrate_directory(std::path::Path::new(path), &steps)?;
+
+    if !dry_run {
+        report.write()?;
+    }
+
+    let files: Vec<String> = report
+        .files
+        .keys()
+        .map(|p| p.display().to_string())
+        .collect();
+    let steps_json: Vec<serde_json::Value> = steps
+        .iter()
+        .map(|m| serde_json::json!({ "from": m.from, "to": m.to, "description": m.description }))
+        .collect();
+
+    match format {
+        OutputFormat::Text => {
+            let route: Vec<&str> = std::iter::once(from)
+                .chain(steps.iter().map(|m| m.to.as_str()))
+                .collect();
+            println!("Migrating {}", route.join(" -> "));
 
-                    total_issues += result.issues.len();
+            let mut file = None;
+            let entries = report
+                .changes
+                .iter()
+                .map(|c| (c, false))
+                .chain(report.warnings.iter().map(|c| (c, true)));
+            let mut entries: Vec<_> = entries.collect();
+            entries.sort_by(|(a, _), (b, _)| a.file.cmp(&b.file));
+            for (change, warning) in entries {
+                if file != Some(&change.file) {
+                    println!("\n{}", change.file);
+                    file = Some(&change.file);
                 }
+                let prefix = if warning { "[WARN] " } else { "" };
+                println!(
+                    "  {}[{}] {}: {}",
+                    prefix, change.location, change.op, change.message
+                );
             }
+
+            println!("\n=== Migration Summary ===");
+            println!(
+                "{} changes, {} warnings in {} files{}",
+                report.changes.len(),
+                report.warnings.len(),
+                files.len(),
+                if dry_run {
+                    " (dry run: nothing written)"
+                } else {
+                    ""
+                }
+            );
+        }
+        OutputFormat::Json => {
+            let document = serde_json::json!({
+                "from": from,
+                "to": to,
+                "steps": steps_json,
+                "changes": report.changes,
+                "warnings": report.warnings,
+                "files": files,
+                "dryRun": dry_run,
+            });
+            print_json(&document, format)?;
+        }
+        OutputFormat::Ndjson => {
+            for (kind, changes) in [("change", &report.changes), ("warning", &report.warnings)] {
+                for change in changes {
+                    let mut line = serde_json::to_value(change)?;
+                    line["kind"] = kind.into();
+                    println!("{}", line);
+                }
+            }
+            println!(
+                "{}",
+                serde_json::json!({
+                    "kind": "summary",
+                    "from": from,
+                    "to": to,
+                    "changes": report.changes.len(),
+                    "warnings": report.warnings.len(),
+                    "files": files,
+                    "dryRun": dry_run,
+                })
+            );
         }
     }
 
-    println!("\n=== Validation Summary ===");
-    println!("Schema version: {}", schema_version);
-    println!(
-        "Total issues: {} ({} errors, {} warnings)",
-        total_issues, total_errors, total_warnings
+    Ok(EXIT_OK)
+}
+
+fn validate_config(
+    path: &str,
+    schema_version: Option<&str>,
+    schemas_dir: Option<&str>,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let config_path = std::path::Path::new(path).join("config.toml");
+    if !config_path.exists() {
+        return Err(format!("No config.toml in {}", path).into());
+    }
+    let layered = scope::layers::LayeredConfig::load(&config_path);
+
+    // The config's own problems are reported below, with locations
+    let (network, mut load_validation) = parser::load_network_from_directory(path)?;
+    load_validation
+        .errors
+        .retain(|e| e.code != "config-invalid");
+    report_load_diagnostics(&load_validation, format);
+
+    // Block types are checked against the given version, or every version on disk
+    // (which also lets the validator check a pinned schemaVersion exists)
+    let selection = schema_selection(path, schema_version, schemas_dir, format)?;
+    let registry = load_schema_libraries(&selection.schemas_dir, schema_version, format)?;
+
+    // Every layer is checked on its own so locations point into the right file
+    let validator = scope::validator::ConfigValidator::new()
+        .with_network(&network)
+        .with_schemas(&registry);
+    let mut validation = parser::validation::ValidationResult::new();
+    match &layered {
+        Ok(layered) => {
+            for layer in &layered.layers {
+                let content = std::fs::read_to_string(&layer.path)?;
+                let result = validator.validate(&content, &layer.path.display().to_string());
+                validation.errors.extend(result.errors);
+                validation.warnings.extend(result.warnings);
+            }
+        }
+        Err(e) => {
+            // Check config.toml on its own; if it is fine, the extends chain is broken
+            let content = std::fs::read_to_string(&config_path)?;
+            let result = validator.validate(&content, &config_path.display().to_string());
+            if result.is_valid() {
+                validation.add_error(
+                    "config-invalid",
+                    e.to_string(),
+                    Some(config_path.display().to_string()),
+                );
+            }
+            validation.errors.extend(result.errors);
+            validation.warnings.extend(result.warnings);
+        }
+    }
+
+    let total_errors = validation.errors.len();
+    let total_warnings = validation.warnings.len();
+    let diagnostics: Vec<Diagnostic> = validation
+        .issues()
+        .map(Diagnostic::from_load_issue)
+        .collect();
+
+    match format {
+        OutputFormat::Text => {
+            if validation.has_issues() {
+                println!("{}", validation);
+            }
+            println!("=== Config Validation Summary ===");
+            println!(
+                "Total issues: {} ({} errors, {} warnings)",
+                diagnostics.len(),
+                total_errors,
+                total_warnings
+            );
+        }
+        OutputFormat::Json => {
+            let document = serde_json::json!({
+                "config": config_path.display().to_string(),
+                "issues": diagnostics,
+                "summary": {
+                    "issues": diagnostics.len(),
+                    "errors": total_errors,
+                    "warnings": total_warnings,
+                },
+            });
+            print_json(&document, format)?;
+        }
+        OutputFormat::Ndjson => {
+            for diagnostic in &diagnostics {
+                println!("{}", serde_json::to_string(diagnostic)?);
+            }
+            println!(
+                "{}",
+                serde_json::json!({
+                    "kind": "summary",
+                    "config": config_path.display().to_string(),
+                    "issues": diagnostics.len(),
+                    "errors": total_errors,
+                    "warnings": total_warnings,
+                })
+            );
+        }
+    }
+
+    Ok(validation_exit_code(&diagnostics))
+}
+
+fn show_config(
+    path: &str,
+    effective: bool,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let config_path = std::path::Path::new(path).join("config.toml");
+    let layered = if effective {
+        scope::layers::LayeredConfig::load_with_user_preferences(&config_path)?
+    } else {
+        scope::layers::LayeredConfig::load(&config_path)?
+    };
+
+    // Without --effective only the network's own entries are shown
+    let entries: Vec<_> = layered
+        .entries()
+        .into_iter()
+        .filter(|(_, _, layer)| effective || layer.kind == scope::layers::LayerKind::Network)
+        .collect();
+
+    match format {
+        OutputFormat::Text => {
+            if effective {
+                println!("Layers (lowest precedence first):");
+                for layer in &layered.layers {
+                    let kind = serde_json::to_value(layer.kind)?;
+                    println!(
+                        "  {:<8} {}",
+                        kind.as_str().unwrap_or_default(),
+                        layer.path.display()
+                    );
+                }
+                println!();
+            }
+
+            let lines: Vec<(String, String)> = entries
+                .iter()
+                .map(|(key, value, layer)| {
+                    (
+                        format!("{} = {}", key, value),
+                        layer.path.display().to_string(),
+                    )
+                })
+                .collect();
+            let width = lines
+                .iter()
+                .map(|(entry, _)| entry.len())
+                .max()
+                .unwrap_or(0);
+            for (entry, source) in lines {
+                if effective {
+                    println!("{:<width$}  # {}", entry, source, width = width);
+                } else {
+                    println!("{}", entry);
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let sources: serde_json::Map<String, serde_json::Value> = entries
+                .iter()
+                .map(|(key, _, layer)| (key.clone(), layer.path.display().to_string().into()))
+                .collect();
+            let config = if effective {
+                serde_json::to_value(&layered.merged)?
+            } else {
+                let own = layered
+                    .layers
+                    .iter()
+                    .find(|l| l.kind == scope::layers::LayerKind::Network)
+                    .map(|l| std::fs::read_to_string(&l.path))
+                    .transpose()?
+                    .map(|content| content.parse::<toml::Table>())
+                    .transpose()?
+                    .unwrap_or_default();
+                serde_json::to_value(own)?
+            };
+            let document = serde_json::json!({
+                "layers": layered.layers,
+                "config": config,
+                "sources": sources,
+            });
+            print_json(&document, format)?;
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn list_properties(
+    path: &str,
+    block_type: Option<&str>,
+    schema_version: Option<&str>,
+    schemas_dir: Option<&str>,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let (network, load_validation) = parser::load_network_from_directory(path)?;
+    report_load_diagnostics(&load_validation, format);
+
+    let selection = schema_selection(path, schema_version, schemas_dir, format)?;
+    let schemas =
+        match load_schema_libraries(&selection.schemas_dir, selection.version.as_deref(), format) {
+            Err(e) if selection.pinned => {
+                if format == OutputFormat::Text {
+                    eprintln!(
+                        "Warning: Pinned schema library not loaded ({}); using every version found",
+                        e
+                    );
+                }
+                load_schema_libraries(&selection.schemas_dir, None, format)?
+            }
+            schemas => schemas?,
+        };
+    let mut registry = scope::registry::PropertyRegistry::new(load_network_config(path)?);
+    for version in schemas.list_versions() {
+        registry = registry.with_schemas(&schemas, version);
+    }
+    let registry = registry.with_network(&network);
+
+    let properties = match block_type {
+        Some(block_type) => registry.for_block_type(block_type),
+        None => registry.list(),
+    };
+
+    match format {
+        OutputFormat::Text => {
+            let rows: Vec<[String; 5]> = properties
+                .iter()
+                .map(|p| {
+                    [
+                        p.name.clone(),
+                        p.dimension.clone().unwrap_or_else(|| "-".to_string()),
+                        p.default_unit.clone().unwrap_or_else(|| "-".to_string()),
+                        p.scopes
+                            .iter()
+                            .map(|s| s.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" -> "),
+                        p.block_types
+                            .iter()
+                            .map(|t| t.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ]
+                })
+                .collect();
+            print_table(
+                ["PROPERTY", "DIMENSION", "UNIT", "SCOPES", "BLOCK TYPES"],
+                rows,
+            );
+        }
+        OutputFormat::Json => print_json(&serde_json::to_value(&properties)?, format)?,
+        OutputFormat::Ndjson => {
+            for property in properties {
+                println!("{}", serde_json::to_string(property)?);
+            }
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn list_schemas(
+    network: &str,
+    schemas_dir: Option<&str>,
+    version: Option<&str>,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let selection = schema_selection(network, None, schemas_dir, format)?;
+    selection.report_config_error(network, format);
+    let registry = schema::registry::SchemaRegistry::new((&selection.schemas_dir).into());
+
+    let Some(version) = version else {
+        let versions = registry.available_versions();
+        let entries: Vec<serde_json::Value> = versions
+            .iter()
+            .map(|version| match registry.library(version) {
+                Ok(_) => serde_json::json!({
+                    "version": version,
+                    "blockTypes": sorted(registry.list_block_types(version)),
+                }),
+                Err(e) => serde_json::json!({ "version": version, "error": e }),
+            })
+            .collect();
+
+        match format {
+            OutputFormat::Text => {
+                if versions.is_empty() {
+                    println!("No schema versions found in {}", selection.schemas_dir);
+                }
+                let rows = versions
+                    .iter()
+                    .map(|version| {
+                        let detail = match registry.library(version) {
+                            Ok(_) => {
+                                format!("{} block types", registry.list_block_types(version).len())
+                            }
+                            Err(e) => format!("failed to load: {}", e),
+                        };
+                        [version.to_string(), detail]
+                    })
+                    .collect::<Vec<_>>();
+                if !rows.is_empty() {
+                    print_table(["VERSION", "BLOCK TYPES"], rows);
+                }
+            }
+            OutputFormat::Json => print_json(&serde_json::Value::Array(entries), format)?,
+            OutputFormat::Ndjson => {
+                for entry in entries {
+                    println!("{}", serde_json::to_string(&entry)?);
+                }
+            }
+        }
+        return Ok(EXIT_OK);
+    };
+
+    registry.library(version)?;
+    let schemas: Vec<&schema::registry::SchemaDefinition> =
+        sorted(registry.list_block_types(version))
+            .into_iter()
+            .filter_map(|block_type| registry.get_schema(version, block_type))
+            .collect();
+
+    match format {
+        OutputFormat::Text => {
+            let rows = schemas
+                .iter()
+                .map(|schema| {
+                    [
+                        schema.block_type.clone(),
+                        schema.extends.clone().unwrap_or_else(|| "-".to_string()),
+                        schema.required_properties.len().to_string(),
+                        schema.optional_properties.len().to_string(),
+                        schema.description.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            print_table(
+                [
+                    "BLOCK TYPE",
+                    "EXTENDS",
+                    "REQUIRED",
+                    "OPTIONAL",
+                    "DESCRIPTION",
+                ],
+                rows,
+            );
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let entries: Vec<serde_json::Value> = schemas
+                .iter()
+                .map(|schema| {
+                    serde_json::json!({
+                        "blockType": schema.block_type,
+                        "extends": schema.extends,
+                        "description": schema.description,
+                        "required": schema.required_properties,
+                        "optional": schema.optional_properties,
+                    })
+                })
+                .collect();
+            if format == OutputFormat::Json {
+                print_json(&serde_json::Value::Array(entries), format)?;
+            } else {
+                for entry in entries {
+                    println!("{}", serde_json::to_string(&entry)?);
+                }
+            }
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn show_schemas(
+    network: &str,
+    schemas_dir: Option<&str>,
+    version: &str,
+    block_type: Option<&str>,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let selection = schema_selection(network, None, schemas_dir, format)?;
+    selection.report_config_error(network, format);
+    let registry = schema::registry::SchemaRegistry::new(selection.schemas_dir.into());
+    let library = registry.library(version)?;
+
+    let schemas: Vec<&schema::registry::SchemaDefinition> = match block_type {
+        // Abstract bases can be shown by name, though blocks can't use them
+        Some(block_type) => vec![library.schemas.get(block_type).ok_or_else(|| {
+            format!(
+                "Unknown block type '{}' in schema version '{}'",
+                block_type, version
+            )
+        })?],
+        None => {
+            let mut schemas: Vec<_> = library.schemas.values().collect();
+            schemas.sort_by(|a, b| a.block_type.cmp(&b.block_type));
+            schemas
+        }
+    };
+
+    match format {
+        OutputFormat::Text => {
+            for (i, schema) in schemas.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                print_schema(schema);
+            }
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let entries: Vec<serde_json::Value> =
+                schemas.iter().map(|schema| schema_json(schema)).collect();
+            match (format, block_type) {
+                (OutputFormat::Ndjson, _) => {
+                    for entry in entries {
+                        println!("{}", serde_json::to_string(&entry)?);
+                    }
+                }
+                (_, Some(_)) => print_json(&entries[0], format)?,
+                (_, None) => print_json(&serde_json::Value::Array(entries), format)?,
+            }
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+fn print_schema(schema: &schema::registry::SchemaDefinition) {
+    let mut title = format!("{} ({})", schema.block_type, schema.version);
+    if schema.is_abstract {
+        title.push_str(" abstract");
+    }
+    if let Some(base) = &schema.extends {
+        title.push_str(&format!(" extends {}", base));
+    }
+    println!("{}", title);
+    if let Some(description) = &schema.description {
+        println!("  {}", description);
+    }
+    if !schema.topology.is_empty() {
+        println!("  topology: {}", serde_json::json!(schema.topology));
+    }
+
+    let mut names: Vec<&String> = schema
+        .required_properties
+        .iter()
+        .chain(&schema.optional_properties)
+        .collect();
+    // Properties with metadata but in neither list are still described
+    let mut described: Vec<&String> = schema
+        .properties
+        .keys()
+        .filter(|name| !names.contains(name))
+        .collect();
+    described.sort();
+    names.extend(described);
+    if names.is_empty() {
+        return;
+    }
+
+    let limit = |limit: &Option<schema::registry::Limit>| {
+        limit
+            .as_ref()
+            .map(|l| l.to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let rows = names
+        .into_iter()
+        .map(|name| {
+            let meta = schema.properties.get(name).cloned().unwrap_or_default();
+            let required = if schema.required_properties.contains(name) {
+                "yes"
+            } else {
+                "no"
+            };
+            [
+                format!("  {}", name),
+                required.to_string(),
+                meta.value_type
+                    .map(|t| t.as_str().to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                meta.dimension.unwrap_or_else(|| "-".to_string()),
+                meta.default_unit.unwrap_or_else(|| "-".to_string()),
+                limit(&meta.min),
+                limit(&meta.max),
+                meta.title.unwrap_or_default(),
+            ]
+        })
+        .collect();
+    print_table(
+        [
+            "  PROPERTY",
+            "REQUIRED",
+            "TYPE",
+            "DIMENSION",
+            "UNIT",
+            "MIN",
+            "MAX",
+            "TITLE",
+        ],
+        rows,
     );
+}
 
-    if total_errors > 0 {
-        std::process::exit(1);
+/// A flattened schema in the shape of a schema file
+fn schema_json(schema: &schema::registry::SchemaDefinition) -> serde_json::Value {
+    let mut json = serde_json::json!({
+        "block_type": schema.block_type,
+        "version": schema.version,
+        "required": schema.required_properties,
+        "optional": schema.optional_properties,
+        "properties": schema.properties.iter().collect::<std::collections::BTreeMap<_, _>>(),
+    });
+    let object = json.as_object_mut().expect("schema JSON is an object");
+    if let Some(description) = &schema.description {
+        object.insert("description".into(), description.clone().into());
+    }
+    if let Some(base) = &schema.extends {
+        object.insert("extends".into(), base.clone().into());
+    }
+    if schema.is_abstract {
+        object.insert("abstract".into(), true.into());
     }
+    if !schema.topology.is_empty() {
+        object.insert("topology".into(), serde_json::json!(schema.topology));
+    }
+    json
+}
 
-    Ok(())
+fn export_json_schemas(
+    network: &str,
+    schemas_dir: Option<&str>,
+    version: Option<&str>,
+    output_dir: Option<&str>,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let selection = schema_selection(network, version, schemas_dir, format)?;
+    selection.report_config_error(network, format);
+    let version = selection.required_version("No version given")?;
+    let registry = schema::registry::SchemaRegistry::new((&selection.schemas_dir).into());
+    let documents = schema::json_schema::export_json_schemas(&registry, version)?;
+
+    let Some(output_dir) = output_dir else {
+        match format {
+            OutputFormat::Ndjson => {
+                for (node_type, document) in &documents {
+                    let entry = serde_json::json!({ "nodeType": node_type, "schema": document });
+                    println!("{}", serde_json::to_string(&entry)?);
+                }
+            }
+            _ => print_json(&serde_json::to_value(&documents)?, format)?,
+        }
+        return Ok(EXIT_OK);
+    };
+
+    let output_dir = std::path::Path::new(output_dir);
+    std::fs::create_dir_all(output_dir)?;
+    let mut written = Vec::new();
+    for (node_type, document) in &documents {
+        let file = output_dir.join(format!("{}.schema.json", node_type));
+        std::fs::write(&file, serde_json::to_string_pretty(document)? + "\n")?;
+        written.push(file.display().to_string());
+    }
+
+    match format {
+        OutputFormat::Text => {
+            for file in &written {
+                println!("Wrote {}", file);
+            }
+        }
+        OutputFormat::Json => print_json(&serde_json::json!({ "files": written }), format)?,
+        OutputFormat::Ndjson => {
+            for file in &written {
+                println!("{}", serde_json::json!({ "file": file }));
+            }
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+fn infer_schemas(
+    paths: &[String],
+    schema_version: &str,
+    output_dir: Option<&str>,
+    force: bool,
+    format: OutputFormat,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut networks = Vec::new();
+    for path in paths {
+        let (network, load_validation) = parser::load_network_from_directory(path)?;
+        report_load_diagnostics(&load_validation, format);
+        networks.push(network);
+    }
+    let inferred =
+        schema::infer::infer_schemas(&networks.iter().collect::<Vec<_>>(), schema_version);
+
+    // Notes are diagnostics: stderr, like loader warnings
+    let mut notes = parser::validation::ValidationResult::new();
+    for schema in &inferred {
+        for note in &schema.notes {
+            notes.add_warning(
+                "inferred-property",
+                note.clone(),
+                Some(schema.block_type.clone()),
+            );
+        }
+    }
+    report_load_diagnostics(&notes, format);
+
+    let Some(output_dir) = output_dir else {
+        match format {
+            OutputFormat::Ndjson => {
+                for schema in &inferred {
+                    println!("{}", serde_json::to_string(&schema.schema)?);
+                }
+            }
+            _ => print_json(
+                &serde_json::Value::Array(inferred.iter().map(|s| s.schema.clone()).collect()),
+                format,
+            )?,
+        }
+        return Ok(EXIT_OK);
+    };
+
+    let output_dir = std::path::Path::new(output_dir);
+    std::fs::create_dir_all(output_dir)?;
+    let mut results = Vec::new();
+    for schema in &inferred {
+        let file = output_dir.join(&schema.file_name);
+        let written = force || !file.exists();
+        if written {
+            std::fs::write(&file, serde_json::to_string_pretty(&schema.schema)? + "\n")?;
+        }
+        results.push((schema, file.display().to_string(), written));
+    }
+
+    match format {
+        OutputFormat::Text => {
+            for (schema, file, written) in &results {
+                if *written {
+                    println!(
+                        "Wrote {} ({} from {} block{})",
+                        file,
+                        schema.block_type,
+                        schema.blocks,
+                        if schema.blocks == 1 { "" } else { "s" }
+                    );
+                } else {
+                    println!("Skipped {} (exists; use --force to overwrite)", file);
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let entries: Vec<serde_json::Value> = results
+                .iter()
+                .map(|(schema, file, written)| {
+                    serde_json::json!({
+                        "blockType": schema.block_type,
+                        "blocks": schema.blocks,
+                        "file": file,
+                        "written": written,
+                    })
+                })
+                .collect();
+            if format == OutputFormat::Json {
+                print_json(&serde_json::Value::Array(entries), format)?;
+            } else {
+                for entry in entries {
+                    println!("{}", serde_json::to_string(&entry)?);
+                }
+            }
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+fn sorted(mut items: Vec<&String>) -> Vec<&String> {
+    items.sort();
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_from_load_issue() {
+        let mut validation = parser::validation::ValidationResult::new();
+        validation.add_error(
+            "parse-failed",
+            "Failed to parse branch-1.toml".to_string(),
+            Some("branch-1.toml".to_string()),
+        );
+        validation.add_warning("schema-unavailable", "Not loaded".to_string(), None);
+
+        let diagnostics: Vec<Diagnostic> = validation
+            .issues()
+            .map(Diagnostic::from_load_issue)
+            .collect();
+        assert_eq!(
+            serde_json::to_value(&diagnostics).unwrap(),
+            serde_json::json!([
+                {"code": "parse-failed", "severity": "error", "location": "branch-1.toml",
+                 "message": "Failed to parse branch-1.toml"},
+                {"code": "schema-unavailable", "severity": "warning", "message": "Not loaded"},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_from_schema_issue() {
+        let registry = {
+            let mut registry = schema::registry::SchemaRegistry::new("schemas".into());
+            registry
+                .load_library_from_files(
+                    "v1.0",
+                    std::collections::HashMap::from([(
+                        "pipe.json".to_string(),
+                        r#"{"block_type": "Pipe", "version": "v1.0", "optional": ["length"],
+                            "properties": {"length": {"type": "integer", "max": 100}}}"#
+                            .to_string(),
+                    )]),
+                )
+                .unwrap();
+            registry
+        };
+        let block = parser::models::Block {
+            quantity: Some(1),
+            type_: "Pipe".to_string(),
+            extra: std::collections::HashMap::from([(
+                "length".to_string(),
+                toml::Value::Integer(250),
+            )]),
+        };
+        let result =
+            schema::validator::SchemaValidator::new(registry).validate_block(&block, "v1.0");
+
+        let diagnostic =
+            Diagnostic::from_schema_issue(&result.issues[0], "branch-1/blocks/0".to_string());
+        let json = serde_json::to_value(&diagnostic).unwrap();
+        assert_eq!(json["code"], "above-maximum");
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["location"], "branch-1/blocks/0");
+        assert_eq!(json["property"], "length");
+        assert_eq!(json["value"], 250);
+        assert!(json["constraint"].is_string());
+    }
+
+    #[test]
+    fn test_exit_code_follows_error_severity() {
+        let mut validation = parser::validation::ValidationResult::new();
+        assert_eq!(validation_exit_code(&[]), EXIT_OK);
+
+        validation.add_warning("unknown-property", "Unknown".to_string(), None);
+        let warnings: Vec<Diagnostic> = validation
+            .issues()
+            .map(Diagnostic::from_load_issue)
+            .collect();
+        assert_eq!(validation_exit_code(&warnings), EXIT_OK);
+
+        // Loader errors fail validation just like schema errors
+        validation.add_error("parse-failed", "Broken".to_string(), None);
+        let errors: Vec<Diagnostic> = validation
+            .issues()
+            .map(Diagnostic::from_load_issue)
+            .collect();
+        assert_eq!(validation_exit_code(&errors), EXIT_VALIDATION_FAILED);
+        assert_ne!(EXIT_VALIDATION_FAILED, EXIT_COMMAND_FAILED);
+    }
+
+    #[test]
+    fn test_migrate_reports_unreadable_config() {
+        let dir = std::env::temp_dir().join(format!("dagger-bad-config-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("config.toml"),
+            "schemaVersion = \"v1.0\"\nbroken = [\n",
+        )
+        .unwrap();
+
+        let path = dir.display().to_string();
+        let error = migrate_network(&path, None, "v1.1", None, true, OutputFormat::Json)
+            .unwrap_err()
+            .to_string();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(error.contains("config.toml can't be read"), "{}", error);
+        assert!(!error.contains("doesn't pin one"), "{}", error);
+    }
+
+    #[test]
+    fn test_migrate_dry_run_writes_nothing() {
+        let dir = std::env::temp_dir().join(format!("dagger-dry-run-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("schemas/v1.0")).unwrap();
+        std::fs::create_dir_all(dir.join("schemas/v1.1/migrations")).unwrap();
+        std::fs::create_dir_all(dir.join("network")).unwrap();
+        for version in ["v1.0", "v1.1"] {
+            std::fs::write(
+                dir.join(format!("schemas/{}/pipe.json", version)),
+                format!(r#"{{"block_type": "Pipe", "version": "{}"}}"#, version),
+            )
+            .unwrap();
+        }
+        std::fs::write(
+            dir.join("schemas/v1.1/migrations/v1.0.json"),
+            r#"{"from": "v1.0", "to": "v1.1", "operations": [
+                {"op": "renameProperty", "from": "len", "to": "length"}]}"#,
+        )
+        .unwrap();
+        let config = "schemaVersion = \"v1.0\"\nschemasDir = \"../schemas\"\n";
+        let branch = "type = \"branch\"\n\n[[block]]\ntype = \"Pipe\"\nlen = 10\n";
+        std::fs::write(dir.join("network/config.toml"), config).unwrap();
+        std::fs::write(dir.join("network/branch-1.toml"), branch).unwrap();
+
+        let network = dir.join("network").display().to_string();
+        let read = |file: &str| std::fs::read_to_string(dir.join("network").join(file)).unwrap();
+        migrate_network(&network, None, "v1.1", None, true, OutputFormat::Json).unwrap();
+        assert_eq!(read("config.toml"), config);
+        assert_eq!(read("branch-1.toml"), branch);
+
+        migrate_network(&network, None, "v1.1", None, false, OutputFormat::Json).unwrap();
+        assert!(read("config.toml").contains("\"v1.1\""));
+        assert!(read("branch-1.toml").contains("length = 10"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,19 +1,85 @@
 #[cfg(not(target_arch = "wasm32"))]
-use crate::dim::processor::UnitProcessor;
+use crate::dim::processor::{dimension_metadata, DimensionMismatch, UnitProcessor};
 use crate::parser::models::*;
 use crate::parser::validation::*;
-#[cfg(not(target_arch = "wasm32"))]
+use crate::schema::defaults::apply_schema_defaults;
 use crate::schema::registry::SchemaRegistry;
+use crate::scope::config::Config;
+use crate::scope::layers::LayeredConfig;
+use crate::scope::resolver::ScopeResolver;
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use toml::Value;
 
+/// Schema a network pins in its config.toml
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinnedSchema {
+    /// `schemaVersion`
+    pub version: String,
+    /// `schemasDir`, resolved against the network directory
+    pub schemas_dir: Option<PathBuf>,
+}
+
+/// Read `schemaVersion` and `schemasDir` from a network's config layers
+pub fn pinned_schema<P: AsRef<Path>>(
+    directory: P,
+) -> Result<Option<PinnedSchema>, Box<dyn std::error::Error>> {
+    let directory = directory.as_ref();
+    let config = LayeredConfig::load(&directory.join("config.toml"))?.config()?;
+    Ok(pinned_schema_of(&config, directory))
+}
+
+fn pinned_schema_of(config: &Config, directory: &Path) -> Option<PinnedSchema> {
+    Some(PinnedSchema {
+        version: config.schema_version.clone()?,
+        schemas_dir: config.schemas_dir.as_ref().map(|dir| directory.join(dir)),
+    })
+}
+
 pub fn load_network_from_directory<P: AsRef<Path>>(
     directory: P,
 ) -> Result<(Network, ValidationResult), Box<dyn std::error::Error>> {
-    let dir_path = directory.as_ref();
+    load_directory(directory.as_ref(), None)
+}
 
+/// Load a network, parsing block properties with the dimensions declared by the
+/// block type's schema in `schema_version` (config.toml [dimensions] still apply
+/// to properties the schema doesn't describe)
+pub fn load_network_from_directory_with_schema<P: AsRef<Path>>(
+    directory: P,
+    registry: &SchemaRegistry,
+    schema_version: &str,
+) -> Result<(Network, ValidationResult), Box<dyn std::error::Error>> {
+    registry.library(schema_version)?;
+    load_directory(directory.as_ref(), Some((registry, schema_version)))
+}
+
+/// Like `load_network_from_directory_with_schema`, then fill in the schema
+/// `default` of every property a block neither sets nor inherits, with
+/// `default` provenance (see `schema::defaults`)
+pub fn load_network_from_directory_with_schema_defaults<P: AsRef<Path>>(
+    directory: P,
+    registry: &SchemaRegistry,
+    schema_version: &str,
+) -> Result<(Network, ValidationResult), Box<dyn std::error::Error>> {
+    let directory = directory.as_ref();
+    let (mut network, validation) =
+        load_network_from_directory_with_schema(directory, registry, schema_version)?;
+
+    // A config that can't be read is already reported; resolve without it
+    let config = LayeredConfig::load(&directory.join("config.toml"))
+        .and_then(|layered| layered.config())
+        .unwrap_or_else(|_| Config::empty());
+    let resolver = ScopeResolver::new(config);
+    apply_schema_defaults(&mut network, &resolver, registry, schema_version)?;
+    Ok((network, validation))
+}
+
+fn load_directory(
+    dir_path: &Path,
+    schema: Option<(&SchemaRegistry, &str)>,
+) -> Result<(Network, ValidationResult), Box<dyn std::error::Error>> {
     // Check if directory exists
     if !dir_path.exists() {
         return Err(format!("Directory does not exist: {}", dir_path.display()).into());
@@ -28,12 +94,80 @@ pub fn load_network_from_directory<P: AsRef<Path>>(
     // Try to read config.toml for network metadata
     let config_path = dir_path.join("config.toml");
     let (network_id, network_label) = if config_path.exists() {
-        let config_content = fs::read_to_string(&config_path)?;
-        parse_network_metadata(&config_content)
+        parse_network_metadata(&fs::read_to_string(&config_path)?)
     } else {
         (None, None)
     };
 
+    // Declared property dimensions may come from any config layer (the extends chain)
+    // A config that can't be read or layered is reported, and the network loads without it
+    let merged = match LayeredConfig::load(&config_path) {
+        Ok(layered) => layered.merged,
+        Err(e) => {
+            validation.add_error(
+                "config-invalid",
+                e.to_string(),
+                Some(config_path.display().to_string()),
+            );
+            toml::Table::new()
+        }
+    };
+    let dimensions = merged
+        .get("dimensions")
+        .map(property_dimensions)
+        .unwrap_or_default();
+
+    // config.toml [properties] are normalized against the same dimensions when resolving
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(properties) = merged.get("properties").and_then(Value::as_table) {
+        let properties: HashMap<String, Value> = properties.clone().into_iter().collect();
+        let mut processor = UnitProcessor::new();
+        if processor
+            .process_hashmap_with_dimensions(&properties, &dimensions)
+            .is_ok()
+        {
+            let mismatches = at_path("properties.", processor.take_mismatches());
+            add_dimension_mismatches(&mut validation, "config.toml", mismatches);
+        }
+    }
+
+    // Without an explicit schema, use the one config.toml pins when its directory is known
+    let pinned_registry;
+    // A config that doesn't deserialize pins nothing; resolving reports why
+    let pinned = Value::Table(merged.clone())
+        .try_into::<Config>()
+        .ok()
+        .and_then(|config| pinned_schema_of(&config, dir_path));
+    let schema = match (schema, pinned) {
+        (
+            None,
+            Some(PinnedSchema {
+                version,
+                schemas_dir: Some(schemas_dir),
+            }),
+        ) => {
+            let mut registry = SchemaRegistry::new(schemas_dir);
+            match registry.load_library(&version) {
+                Ok(()) => {
+                    pinned_registry = (registry, version);
+                    Some((&pinned_registry.0, pinned_registry.1.as_str()))
+                }
+                Err(e) => {
+                    validation.add_warning(
+                        "schema-unavailable",
+                        format!(
+                            "Pinned schema version '{}' could not be loaded: {}",
+                            version, e
+                        ),
+                        Some(config_path.display().to_string()),
+                    );
+                    None
+                }
+            }
+        }
+        (schema, _) => schema,
+    };
+
     // Scan directory for TOML files
     let entries = fs::read_dir(dir_path)?;
 
@@ -47,10 +181,11 @@ pub fn load_network_from_directory<P: AsRef<Path>>(
                 continue;
             }
 
-            match load_node_from_file(&path) {
+            match load_node_from_file(&path, &dimensions, schema, &mut validation) {
                 Ok(node) => nodes.push(node),
                 Err(e) => {
                     validation.add_error(
+                        "parse-failed",
                         format!("Failed to parse {}: {}", path.display(), e),
                         Some(path.display().to_string()),
                     );
@@ -83,6 +218,30 @@ fn parse_network_metadata(config_content: &str) -> (Option<String>, Option<Strin
     }
 }
 
+/// Parse the property -> dimension table (`[dimensions]`) from config content
+fn parse_property_dimensions(config_content: &str) -> HashMap<String, String> {
+    toml::from_str::<Value>(config_content)
+        .ok()
+        .and_then(|config| config.get("dimensions").map(property_dimensions))
+        .unwrap_or_default()
+}
+
+fn property_dimensions(dimensions: &Value) -> HashMap<String, String> {
+    dimensions
+        .as_table()
+        .map(|table| {
+            table
+                .iter()
+                .filter_map(|(property, dimension)| {
+                    dimension
+                        .as_str()
+                        .map(|d| (property.clone(), d.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 /// Load a network from file contents (filename -> content map)
 /// This is used when files are read in Node.js and passed to WASM
 pub fn load_network_from_files(
@@ -92,12 +251,16 @@ pub fn load_network_from_files(
     let mut nodes = Vec::new();
     let mut validation = ValidationResult::new();
 
-    // Parse config for network metadata
+    // Parse config for network metadata and declared property dimensions
     let (network_id, network_label) = if let Some(ref config) = config_content {
         parse_network_metadata(config)
     } else {
         (None, None)
     };
+    let dimensions = config_content
+        .as_deref()
+        .map(parse_property_dimensions)
+        .unwrap_or_default();
 
     // Process each TOML file
     for (filename, content) in files {
@@ -113,10 +276,11 @@ pub fn load_network_from_files(
             .ok_or_else(|| format!("Invalid filename: {}", filename))?
             .to_string();
 
-        match load_node_from_content(&content, &id, &filename) {
+        match load_node_from_content(&content, &id, &filename, &dimensions, None, &mut validation) {
             Ok(node) => nodes.push(node),
             Err(e) => {
                 validation.add_error(
+                    "parse-failed",
                     format!("Failed to parse {}: {}", filename, e),
                     Some(filename.clone()),
                 );
@@ -130,7 +294,40 @@ pub fn load_network_from_files(
     Ok((network, validation))
 }
 
-fn load_node_from_file<P: AsRef<Path>>(path: P) -> Result<NodeData, Box<dyn std::error::Error>> {
+/// Like `load_network_from_files`, then fill in the schema `default` of every
+/// property a block neither sets nor inherits, with `default` provenance
+pub fn load_network_from_files_with_schema_defaults(
+    files: HashMap<String, String>,
+    config_content: Option<String>,
+    registry: &SchemaRegistry,
+    schema_version: &str,
+) -> Result<(Network, ValidationResult), Box<dyn std::error::Error>> {
+    registry.library(schema_version)?;
+    let (mut network, mut validation) = load_network_from_files(files, config_content.clone())?;
+
+    let config = match config_content.as_deref().map(Config::load_from_str) {
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            validation.add_error(
+                "config-invalid",
+                e.to_string(),
+                Some("config.toml".to_string()),
+            );
+            Config::empty()
+        }
+        None => Config::empty(),
+    };
+    let resolver = ScopeResolver::new(config);
+    apply_schema_defaults(&mut network, &resolver, registry, schema_version)?;
+    Ok((network, validation))
+}
+
+fn load_node_from_file<P: AsRef<Path>>(
+    path: P,
+    dimensions: &HashMap<String, String>,
+    schema: Option<(&SchemaRegistry, &str)>,
+    validation: &mut ValidationResult,
+) -> Result<NodeData, Box<dyn std::error::Error>> {
     let path = path.as_ref();
     let content = fs::read_to_string(path)?;
 
@@ -147,13 +344,16 @@ fn load_node_from_file<P: AsRef<Path>>(path: P) -> Result<NodeData, Box<dyn std:
         .unwrap_or("unknown")
         .to_string();
 
-    load_node_from_content(&content, &id, &filename)
+    load_node_from_content(&content, &id, &filename, dimensions, schema, validation)
 }
 
 fn load_node_from_content(
     content: &str,
     id: &str,
-    _filename: &str,
+    filename: &str,
+    dimensions: &HashMap<String, String>,
+    schema: Option<(&SchemaRegistry, &str)>,
+    validation: &mut ValidationResult,
 ) -> Result<NodeData, Box<dyn std::error::Error>> {
     let value: Value = toml::from_str(content)?;
 
@@ -196,51 +396,65 @@ fn load_node_from_content(
         }
     };
 
-    // Process unit strings in the node (no schema registry at this level for now)
+    // Process unit strings in the node, using the block schemas when given
     // Unit processing is disabled for WASM builds (wasmtime can't be compiled to WASM)
     #[cfg(not(target_arch = "wasm32"))]
     {
-        process_units_in_node(&mut node, None, None)?;
+        let (registry, version) = schema.unzip();
+        let mismatches = process_units_in_node(&mut node, registry, version, dimensions)?;
+        add_dimension_mismatches(validation, filename, mismatches);
     }
+    #[cfg(target_arch = "wasm32")]
+    let _ = (filename, dimensions, schema, validation);
 
     Ok(node)
 }
 
 /// Process unit strings in a node, converting them to normalized values
 /// Optionally uses schema registry for dimension-aware parsing and validation
+/// Properties declared in config.toml [dimensions] are parsed and validated against
+/// that dimension wherever the schema doesn't say otherwise
+/// Returns the unit strings that contradict their dimension, keyed by property path
+/// within the node (e.g. "block[0].pressure")
 #[cfg(not(target_arch = "wasm32"))]
 fn process_units_in_node(
     node: &mut NodeData,
     schema_registry: Option<&SchemaRegistry>,
     schema_version: Option<&str>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    dimensions: &HashMap<String, String>,
+) -> Result<Vec<(String, DimensionMismatch)>, Box<dyn std::error::Error>> {
     let mut processor = UnitProcessor::new();
+    let declared = dimension_metadata(dimensions);
 
     match node {
         NodeData::Branch(branch) => {
-            // Process base node extra properties (no schema for nodes, use regular processing)
-            branch.base.extra = processor.process_hashmap(&branch.base.extra)?;
+            // Process base node extra properties (no schema for nodes, only declared dimensions)
+            branch.base.extra =
+                processor.process_hashmap_with_schema(&branch.base.extra, &declared)?;
+            let mut mismatches = at_path("", processor.take_mismatches());
 
             // Process blocks with schema-aware processing if available
-            for block in &mut branch.blocks {
-                if let (Some(registry), Some(version)) = (schema_registry, schema_version) {
-                    // Try to get schema for this block type
-                    if let Some(schema) = registry.get_schema(version, &block.type_) {
-                        // Use schema-aware processing
-                        block.extra = processor
-                            .process_hashmap_with_schema(&block.extra, &schema.properties)?;
-                    } else {
-                        // No schema found, use regular processing
-                        block.extra = processor.process_hashmap(&block.extra)?;
-                    }
-                } else {
-                    // No schema registry, use regular processing
-                    block.extra = processor.process_hashmap(&block.extra)?;
+            for (index, block) in branch.blocks.iter_mut().enumerate() {
+                let schema = schema_registry
+                    .zip(schema_version)
+                    .and_then(|(registry, version)| registry.get_schema(version, &block.type_));
+
+                // Schema metadata takes precedence over declared dimensions
+                let mut metadata = declared.clone();
+                if let Some(schema) = schema {
+                    metadata.extend(schema.properties.clone());
                 }
+                block.extra = processor.process_hashmap_with_schema(&block.extra, &metadata)?;
+                mismatches.extend(at_path(
+                    &format!("block[{}].", index),
+                    processor.take_mismatches(),
+                ));
             }
+            return Ok(mismatches);
         }
         NodeData::Group(group) => {
-            group.base.extra = processor.process_hashmap(&group.base.extra)?;
+            group.base.extra =
+                processor.process_hashmap_with_schema(&group.base.extra, &declared)?;
         }
         NodeData::GeographicAnchor(anchor) => {
             anchor.base.extra = processor.process_hashmap(&anchor.base.extra)?;
@@ -253,7 +467,34 @@ fn process_units_in_node(
         }
     }
 
-    Ok(())
+    Ok(at_path("", processor.take_mismatches()))
+}
+
+/// Report unit strings that contradict their property's dimension as warnings
+#[cfg(not(target_arch = "wasm32"))]
+fn add_dimension_mismatches(
+    validation: &mut ValidationResult,
+    filename: &str,
+    mismatches: Vec<(String, DimensionMismatch)>,
+) {
+    for (path, mismatch) in mismatches {
+        validation.add_warning(
+            "dimension-mismatch",
+            format!(
+                "'{}' is not a {} ({}); kept as written",
+                mismatch.value, mismatch.expected, mismatch.reason
+            ),
+            Some(format!("{}:{}", filename, path)),
+        );
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn at_path(prefix: &str, mismatches: Vec<DimensionMismatch>) -> Vec<(String, DimensionMismatch)> {
+    mismatches
+        .into_iter()
+        .map(|m| (format!("{}{}", prefix, m.property), m))
+        .collect()
 }
 
 fn build_network(
@@ -277,6 +518,7 @@ fn build_network(
                 // Validate target exists
                 if !node_map.contains_key(&outgoing.target) {
                     validation.add_warning(
+                        "unknown-target",
                         format!("Outgoing target '{}' does not exist", outgoing.target),
                         Some(format!("{}/outgoing[{}]/target", branch.base.id, idx)),
                     );
@@ -297,6 +539,7 @@ fn build_network(
         if let Some(parent_id) = node.base().parent_id.as_ref() {
             if !node_map.contains_key(parent_id) {
                 validation.add_warning(
+                    "unknown-parent",
                     format!("Parent ID '{}' does not exist", parent_id),
                     Some(format!("{}/parentId", node.id())),
                 );
@@ -312,5 +555,22 @@ fn build_network(
         edges,
     };
 
+    // Validate parentId links don't form a cycle (each cycle reported once)
+    let mut reported_cycles: Vec<Vec<String>> = Vec::new();
+    for node in &network.nodes {
+        if let Err(e) = network.group_ancestors(node.base().parent_id.as_deref()) {
+            let mut members = e.cycle[1..].to_vec();
+            members.sort();
+            if !reported_cycles.contains(&members) {
+                validation.add_error(
+                    "parent-cycle",
+                    e.to_string(),
+                    Some(format!("{}/parentId", node.id())),
+                );
+                reported_cycles.push(members);
+            }
+        }
+    }
+
     Ok(network)
 }
@@ -427,3 +427,55 @@ pub struct Network {
     pub nodes: Vec<NodeData>,
     pub edges: Vec<Edge>,
 }
+
+/// Following `parentId` links revisited a node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParentCycleError {
+    /// Node IDs along the cycle, starting and ending with the repeated node
+    pub cycle: Vec<String>,
+}
+
+impl std::fmt::Display for ParentCycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Parent cycle detected: {}", self.cycle.join(" -> "))
+    }
+}
+
+impl std::error::Error for ParentCycleError {}
+
+impl Network {
+    pub fn find_node(&self, id: &str) -> Option<&NodeData> {
+        self.nodes.iter().find(|n| n.id() == id)
+    }
+
+    /// Group ancestors reached by following `parentId` links from `parent_id`, nearest first
+    /// Non-group ancestors (e.g. geographic windows) are walked through but not returned
+    pub fn group_ancestors(
+        &self,
+        parent_id: Option<&str>,
+    ) -> Result<Vec<&GroupNode>, ParentCycleError> {
+        let mut visited: Vec<&str> = Vec::new();
+        let mut groups = Vec::new();
+        let mut current = parent_id;
+
+        while let Some(id) = current {
+            if let Some(pos) = visited.iter().position(|v| *v == id) {
+                let mut cycle: Vec<String> = visited[pos..].iter().map(|v| v.to_string()).collect();
+                cycle.push(id.to_string());
+                return Err(ParentCycleError { cycle });
+            }
+            visited.push(id);
+
+            // A dangling parent ends the chain (reported separately by the loader)
+            let Some(node) = self.find_node(id) else {
+                break;
+            };
+            if let NodeData::Group(group) = node {
+                groups.push(group);
+            }
+            current = node.base().parent_id.as_deref();
+        }
+
+        Ok(groups)
+    }
+}
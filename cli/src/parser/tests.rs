@@ -95,4 +95,211 @@ length = 10
             }
         }
     }
+
+    #[test]
+    fn test_dimension_mismatch_is_a_load_warning() {
+        let files = HashMap::from([(
+            "branch-1.toml".to_string(),
+            r#"
+type = "branch"
+position = { x = 0, y = 0 }
+
+[[block]]
+type = "Pipe"
+length = "5 bar"
+"#
+            .to_string(),
+        )]);
+        let config = "[dimensions]\nlength = \"length\"\n".to_string();
+
+        let (network, validation) = load_network_from_files(files, Some(config)).unwrap();
+        let warning = validation
+            .warnings
+            .iter()
+            .find(|w| w.code == "dimension-mismatch")
+            .expect("Expected a dimension-mismatch warning");
+        assert_eq!(
+            warning.location.as_deref(),
+            Some("branch-1.toml:block[0].length")
+        );
+
+        // The value is kept as written
+        let Some(NodeData::Branch(branch)) = network.nodes.first() else {
+            panic!("Expected Branch node");
+        };
+        assert_eq!(
+            branch.blocks[0].extra.get("length"),
+            Some(&Value::String("5 bar".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_load_network_with_schema_requires_loaded_version() {
+        use super::super::loader::load_network_from_directory_with_schema;
+        use crate::schema::registry::SchemaRegistry;
+
+        let dir = std::env::temp_dir().join(format!("dagger-schema-load-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("branch-1.toml"),
+            "type = \"branch\"\nposition = { x = 0, y = 0 }\n\n[[block]]\ntype = \"Pipe\"\nlength = \"10 bar\"\n",
+        )
+        .unwrap();
+
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry
+            .load_library_from_files(
+                "v1.0",
+                HashMap::from([(
+                    "pipe.json".to_string(),
+                    r#"{"block_type": "Pipe", "version": "v1.0", "required": ["length"], "optional": [],
+                        "properties": {"length": {"dimension": "length"}}}"#
+                        .to_string(),
+                )]),
+            )
+            .unwrap();
+
+        let err = load_network_from_directory_with_schema(&dir, &registry, "v2.0")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("v2.0"), "{}", err);
+
+        let length = |network: &Network| match network.nodes.first() {
+            Some(NodeData::Branch(branch)) => branch.blocks[0].extra.get("length").cloned(),
+            _ => panic!("Expected Branch node"),
+        };
+
+        // Without the schema any unit is normalized
+        let (network, _) = super::super::loader::load_network_from_directory(&dir).unwrap();
+        assert!(matches!(length(&network), Some(Value::Float(_))));
+
+        // The schema's dimension rejects a pressure given for a length
+        let (network, validation) =
+            load_network_from_directory_with_schema(&dir, &registry, "v1.0").unwrap();
+        assert!(validation.is_valid());
+        assert_eq!(validation.warnings[0].code, "dimension-mismatch");
+        assert_eq!(length(&network), Some(Value::String("10 bar".to_string())));
+    }
+
+    #[test]
+    fn test_schema_defaults_loader_option() {
+        use super::super::loader::{
+            load_network_from_directory_with_schema_defaults,
+            load_network_from_files_with_schema_defaults,
+        };
+        use crate::schema::defaults::is_schema_default;
+        use crate::schema::registry::SchemaRegistry;
+
+        let dir = std::env::temp_dir().join(format!("dagger-defaults-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let branch =
+            "type = \"branch\"\nposition = { x = 0, y = 0 }\n\n[[block]]\ntype = \"CaptureUnit\"\n";
+        std::fs::write(dir.join("branch-1.toml"), branch).unwrap();
+        std::fs::write(dir.join("config.toml"), "[properties]\nefficiency = 0.7\n").unwrap();
+
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry
+            .load_library_from_files(
+                "v1.0",
+                HashMap::from([(
+                    "capture.json".to_string(),
+                    r#"{"block_type": "CaptureUnit", "version": "v1.0",
+                        "optional": ["technology", "efficiency"],
+                        "properties": {"technology": {"default": "amine"},
+                                       "efficiency": {"default": 0.9}}}"#
+                        .to_string(),
+                )]),
+            )
+            .unwrap();
+        let block = |network: &Network| match network.nodes.first() {
+            Some(NodeData::Branch(branch)) => branch.blocks[0].clone(),
+            _ => panic!("Expected Branch node"),
+        };
+
+        // config.toml's efficiency is inherited, so only the technology is filled in
+        let (network, _) =
+            load_network_from_directory_with_schema_defaults(&dir, &registry, "v1.0").unwrap();
+        let loaded = block(&network);
+        assert!(is_schema_default(&loaded, "technology"));
+        assert!(!loaded.extra.contains_key("efficiency"));
+
+        // A config that doesn't parse is reported, and every default applies
+        let files = HashMap::from([("branch-1.toml".to_string(), branch.to_string())]);
+        let (network, validation) = load_network_from_files_with_schema_defaults(
+            files,
+            Some("[properties".to_string()),
+            &registry,
+            "v1.0",
+        )
+        .unwrap();
+        assert_eq!(validation.errors[0].code, "config-invalid");
+        let loaded = block(&network);
+        assert!(is_schema_default(&loaded, "technology"));
+        assert_eq!(loaded.extra.get("efficiency"), Some(&Value::Float(0.9)));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pinned_schema_is_picked_up_by_the_loader() {
+        use super::super::loader::{load_network_from_directory, pinned_schema};
+
+        let dir = std::env::temp_dir().join(format!("dagger-pinned-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("schemas/v1.0")).unwrap();
+        std::fs::write(
+            dir.join("schemas/v1.0/pipe.json"),
+            r#"{"block_type": "Pipe", "version": "v1.0", "required": [], "optional": []}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("branch-1.toml"),
+            "type = \"branch\"\nposition = { x = 0, y = 0 }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("config.toml"),
+            "schemaVersion = \"v1.0\"\nschemasDir = \"schemas\"\n",
+        )
+        .unwrap();
+
+        let pinned = pinned_schema(&dir).unwrap().unwrap();
+        assert_eq!(pinned.version, "v1.0");
+        assert_eq!(pinned.schemas_dir, Some(dir.join("schemas")));
+
+        let (_, validation) = load_network_from_directory(&dir).unwrap();
+        assert!(!validation.has_issues(), "{}", validation);
+
+        // A pinned version that isn't on disk is reported, not fatal
+        std::fs::write(
+            dir.join("config.toml"),
+            "schemaVersion = \"v2.0\"\nschemasDir = \"schemas\"\n",
+        )
+        .unwrap();
+        let (network, validation) = load_network_from_directory(&dir).unwrap();
+        assert_eq!(network.nodes.len(), 1);
+        assert_eq!(validation.warnings[0].code, "schema-unavailable");
+    }
+
+    #[test]
+    fn test_broken_config_is_a_load_error() {
+        use super::super::loader::load_network_from_directory;
+
+        let dir = std::env::temp_dir().join(format!("dagger-broken-config-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("branch-1.toml"),
+            "type = \"branch\"\nposition = { x = 0, y = 0 }\n",
+        )
+        .unwrap();
+
+        for config in ["[properties\n", "extends = \"../missing.toml\"\n"] {
+            std::fs::write(dir.join("config.toml"), config).unwrap();
+            let (network, validation) = load_network_from_directory(&dir).unwrap();
+            assert_eq!(network.nodes.len(), 1);
+            assert_eq!(validation.errors[0].code, "config-invalid");
+        }
+    }
 }
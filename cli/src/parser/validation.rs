@@ -1,3 +1,4 @@
+use serde::Serialize;
 use std::fmt;
 
 #[derive(Debug, Clone)]
@@ -6,14 +7,17 @@ pub struct ValidationResult {
     pub warnings: Vec<ValidationIssue>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ValidationIssue {
+    /// Stable machine-readable identifier (e.g. "unknown-target")
+    pub code: &'static str,
     pub severity: IssueSeverity,
     pub message: String,
     pub location: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum IssueSeverity {
     Error,
     Warning,
@@ -27,16 +31,18 @@ impl ValidationResult {
         }
     }
 
-    pub fn add_error(&mut self, message: String, location: Option<String>) {
+    pub fn add_error(&mut self, code: &'static str, message: String, location: Option<String>) {
         self.errors.push(ValidationIssue {
+            code,
             severity: IssueSeverity::Error,
             message,
             location,
         });
     }
 
-    pub fn add_warning(&mut self, message: String, location: Option<String>) {
+    pub fn add_warning(&mut self, code: &'static str, message: String, location: Option<String>) {
         self.warnings.push(ValidationIssue {
+            code,
             severity: IssueSeverity::Warning,
             message,
             location,
@@ -50,6 +56,11 @@ impl ValidationResult {
     pub fn has_issues(&self) -> bool {
         !self.errors.is_empty() || !self.warnings.is_empty()
     }
+
+    /// All issues, errors first
+    pub fn issues(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.errors.iter().chain(self.warnings.iter())
+    }
 }
 
 impl Default for ValidationResult {
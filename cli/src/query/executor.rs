@@ -4,6 +4,7 @@ use crate::parser::models::*;
 use crate::query::parser::{FilterOperator, ParseError, QueryPath};
 #[cfg(not(target_arch = "wasm32"))]
 use crate::schema::registry::SchemaRegistry;
+use crate::scope::resolver::ResolveError;
 use serde_json::Value as JsonValue;
 use toml::Value as TomlValue;
 
@@ -14,6 +15,8 @@ pub enum QueryError {
     IndexOutOfRange(usize, usize),
     InvalidType(String),
     ParseError(ParseError),
+    /// Scope resolution failed (parent cycle, upstream conflict, ...)
+    Resolve(ResolveError),
 }
 
 impl std::fmt::Display for QueryError {
@@ -26,6 +29,7 @@ impl std::fmt::Display for QueryError {
             }
             QueryError::InvalidType(msg) => write!(f, "Invalid type: {}", msg),
             QueryError::ParseError(e) => write!(f, "Parse error: {}", e),
+            QueryError::Resolve(e) => write!(f, "{}", e),
         }
     }
 }
@@ -38,6 +42,12 @@ impl From<ParseError> for QueryError {
     }
 }
 
+impl From<ResolveError> for QueryError {
+    fn from(e: ResolveError) -> Self {
+        QueryError::Resolve(e)
+    }
+}
+
 pub struct QueryExecutor<'a> {
     network: &'a Network,
     scope_resolver: Option<&'a crate::scope::resolver::ScopeResolver>,
@@ -156,6 +166,11 @@ impl<'a> QueryExecutor<'a> {
                 self.get_node(id)
             }
             QueryPath::Property(name, inner) => {
+                // Network-level collections (also reached through filters, e.g. "nodes[type=branch]")
+                if matches!(inner.as_ref(), QueryPath::Node(id) if id == "network") {
+                    return self.execute_network_query(name);
+                }
+
                 let value = self.execute_with_context(inner, context)?;
                 // If we're accessing a property on a block and it's not found, try scope resolution
                 // Check if this looks like a block object (has "type" property)
@@ -260,7 +275,8 @@ impl<'a> QueryExecutor<'a> {
                 let blocks: Vec<JsonValue> = branch
                     .blocks
                     .iter()
-                    .map(|b| {
+                    .enumerate()
+                    .map(|(index, b)| {
                         let mut block_obj = serde_json::Map::new();
                         block_obj.insert("type".to_string(), JsonValue::String(b.type_.clone()));
                         if let Some(quantity) = b.quantity {
@@ -283,7 +299,7 @@ impl<'a> QueryExecutor<'a> {
                             }
 
                             // Build unit preferences with original strings
-                            let mut block_unit_prefs = self.unit_preferences.clone();
+                            let mut block_unit_prefs = self.unit_preferences_for(branch, index);
                             block_unit_prefs.original_strings = original_strings;
 
                             // Add extra properties with unit formatting
@@ -322,6 +338,7 @@ impl<'a> QueryExecutor<'a> {
                         #[cfg(target_arch = "wasm32")]
                         {
                             // In WASM builds, just add properties as-is (no unit formatting)
+                            let _ = index;
                             for (key, value) in &b.extra {
                                 block_obj.insert(key.clone(), toml_to_json(value));
                             }
@@ -617,6 +634,23 @@ where
 }
 
 impl<'a> QueryExecutor<'a> {
+    /// Unit preferences for a block: `unitPreferences` set on its nodes (resolved
+    /// through the scope chain) ranked above the configured ones
+    #[cfg(not(target_arch = "wasm32"))]
+    fn unit_preferences_for(&self, branch: &BranchNode, block_index: usize) -> UnitPreferences {
+        let scoped = self.scope_resolver.and_then(|resolver| {
+            let ctx =
+                crate::scope::resolver::BlockContext::in_network(self.network, branch, block_index)
+                    .ok()?;
+            resolver.resolve_unit_preferences(&ctx).ok().flatten()
+        });
+
+        match scoped {
+            Some(scoped) => self.unit_preferences.with_scoped(&scoped),
+            None => self.unit_preferences.clone(),
+        }
+    }
+
     fn resolve_scoped_property_from_context(
         &self,
         property: &str,
@@ -647,58 +681,68 @@ impl<'a> QueryExecutor<'a> {
             })
             .ok_or_else(|| QueryError::NodeNotFound(node_id.clone()))?;
 
-        // Get the block
-        let block = branch_node
-            .blocks
-            .get(block_index)
-            .ok_or(QueryError::IndexOutOfRange(
+        // Check the block exists
+        if block_index >= branch_node.blocks.len() {
+            return Err(QueryError::IndexOutOfRange(
                 block_index,
                 branch_node.blocks.len(),
-            ))?;
+            ));
+        }
 
-        // Find the group if parent_id exists
-        let group = branch_node.base.parent_id.as_ref().and_then(|parent_id| {
-            self.network.nodes.iter().find_map(|n| match n {
-                NodeData::Group(g) if g.base.id == *parent_id => Some(g),
-                _ => None,
-            })
-        });
+        // Block with its enclosing groups and upstream network
+        let ctx = crate::scope::resolver::BlockContext::in_network(
+            self.network,
+            branch_node,
+            block_index,
+        )?;
 
         // Parse explicit scopes from query string to ScopeLevel enums
         let scope_levels: Vec<crate::scope::config::ScopeLevel> = explicit_scopes
             .iter()
-            .filter_map(|s| match s.to_lowercase().as_str() {
-                "block" => Some(crate::scope::config::ScopeLevel::Block),
-                "branch" => Some(crate::scope::config::ScopeLevel::Branch),
-                "group" => Some(crate::scope::config::ScopeLevel::Group),
-                "global" => Some(crate::scope::config::ScopeLevel::Global),
-                _ => None,
-            })
+            .filter_map(|s| s.parse().ok())
             .collect();
 
         // Resolve the property using explicit scopes if provided, otherwise use config defaults
         let value = if !scope_levels.is_empty() {
             resolver
-                .resolve_property_with_explicit_scopes(
+                .resolve_property_with_explicit_scopes(property, &ctx, &scope_levels)
+                .map(|resolved| resolved.map(|(v, _)| v))
+        } else {
+            resolver.resolve_property(property, &ctx)
+        }?;
+
+        // Convert TOML Value to JSON Value
+        let value = match value {
+            Some(v) => toml_to_json(&v),
+            None => {
+                return Err(QueryError::PropertyNotFound(format!(
+                    "Property '{}' not found in any scope",
+                    property
+                )))
+            }
+        };
+
+        // Apply unit preferences; inherited values are normalized, so the base unit
+        // comes from the property's dimension (schema or config.toml [dimensions])
+        #[cfg(not(target_arch = "wasm32"))]
+        let value = {
+            let property_metadata = self
+                .schema_registry
+                .zip(self.schema_version)
+                .and_then(|(reg, v)| reg.get_schema(v, &ctx.block.type_))
+                .and_then(|schema| schema.properties.get(property));
+            UnitFormatter::new()
+                .format_property(
                     property,
-                    block,
-                    branch_node,
-                    group,
-                    &scope_levels,
+                    &value,
+                    Some(&ctx.block.type_),
+                    &self.unit_preferences_for(ctx.branch, block_index),
+                    property_metadata,
                 )
-                .map(|(v, _)| v)
-        } else {
-            resolver.resolve_property(property, block, branch_node, group)
+                .unwrap_or(value)
         };
 
-        // Convert TOML Value to JSON Value
-        match value {
-            Some(v) => Ok(toml_to_json(&v)),
-            None => Err(QueryError::PropertyNotFound(format!(
-                "Property '{}' not found in any scope",
-                property
-            ))),
-        }
+        Ok(value)
     }
 }
 
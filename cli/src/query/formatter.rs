@@ -1,5 +1,435 @@
 use serde_json::Value;
+use std::collections::HashMap;
 
 pub fn format_query_result(value: &Value) -> String {
     serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
 }
+
+/// Tabular output formats for query results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    Csv,
+    Markdown,
+    Toml,
+    Yaml,
+    /// Aligned plain-text table for terminals
+    Table,
+}
+
+impl std::str::FromStr for TableFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(TableFormat::Csv),
+            "md" | "markdown" => Ok(TableFormat::Markdown),
+            "toml" => Ok(TableFormat::Toml),
+            "yaml" | "yml" => Ok(TableFormat::Yaml),
+            "table" => Ok(TableFormat::Table),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+/// Rows and columns inferred from a query result
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabularResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<HashMap<String, Value>>,
+}
+
+/// Format a query result as a table
+/// Arrays of objects become one row per element; anything else becomes a single row
+pub fn format_query_result_as(value: &Value, format: TableFormat) -> Result<String, String> {
+    let table = to_tabular(value);
+
+    match format {
+        TableFormat::Csv => Ok(format_csv(&table)),
+        TableFormat::Markdown => Ok(format_markdown(&table)),
+        TableFormat::Toml => format_toml(&table),
+        TableFormat::Yaml => Ok(format_yaml(&table)),
+        TableFormat::Table => Ok(format_plain_table(&table)),
+    }
+}
+
+/// Infer columns from the union of keys (in first-seen order), flattening nested
+/// objects into dotted keys (e.g. "position.x") and splitting unit-bearing
+/// values ("0.5 km") into a value column and a "<column>_unit" column. Only
+/// strings the unit parser accepts are split, so labels like "2 Compressor
+/// Station" stay whole
+pub fn to_tabular(value: &Value) -> TabularResult {
+    let records: Vec<Value> = match value {
+        Value::Array(items) => items.clone(),
+        other => vec![other.clone()],
+    };
+
+    let mut columns: Vec<String> = Vec::new();
+    let mut rows: Vec<HashMap<String, Value>> = Vec::new();
+
+    for record in &records {
+        let mut row = Vec::new();
+        match record {
+            Value::Object(_) => flatten_into(record, "", &mut row),
+            scalar => row.push(("value".to_string(), scalar.clone())),
+        }
+        for (key, _) in &row {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+        rows.push(row.into_iter().collect());
+    }
+
+    // Split columns holding unit strings into value + unit columns
+    let mut split_columns = Vec::new();
+    for column in columns {
+        let has_units = rows
+            .iter()
+            .any(|row| row.get(&column).and_then(split_unit_value).is_some());
+
+        if !has_units {
+            split_columns.push(column);
+            continue;
+        }
+
+        let unit_column = format!("{}_unit", column);
+        for row in &mut rows {
+            if let Some((number, unit)) = row.get(&column).and_then(split_unit_value) {
+                row.insert(column.clone(), number);
+                row.insert(unit_column.clone(), Value::String(unit));
+            }
+        }
+        split_columns.push(column);
+        split_columns.push(unit_column);
+    }
+
+    TabularResult {
+        columns: split_columns,
+        rows,
+    }
+}
+
+fn flatten_into(value: &Value, prefix: &str, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, nested) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_into(nested, &path, out);
+            }
+        }
+        other => out.push((prefix.to_string(), other.clone())),
+    }
+}
+
+/// Split "<number> <unit>" strings into a numeric value and a unit
+fn split_unit_value(value: &Value) -> Option<(Value, String)> {
+    let s = value.as_str()?.trim();
+    let (number, unit) = s.split_once(char::is_whitespace)?;
+    let unit = unit.trim();
+    if unit.is_empty() || unit.parse::<f64>().is_ok() {
+        return None;
+    }
+    let number = number.parse::<f64>().ok()?;
+    if !is_unit_string(s) {
+        return None;
+    }
+    let number = serde_json::Number::from_f64(number).map(Value::Number)?;
+    Some((number, unit.to_string()))
+}
+
+/// Whether the unit parser accepts `s` as a quantity
+#[cfg(not(target_arch = "wasm32"))]
+fn is_unit_string(s: &str) -> bool {
+    crate::dim::DimParser::new()
+        .and_then(|mut parser| parser.parse_unit_string(s))
+        .is_ok()
+}
+
+/// WASM builds have no unit parser, so nothing is split
+#[cfg(target_arch = "wasm32")]
+fn is_unit_string(_s: &str) -> bool {
+    false
+}
+
+/// Render a cell for text-based formats
+fn cell_text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn format_csv(table: &TabularResult) -> String {
+    let escape = |field: &str| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    };
+
+    let mut lines = vec![table
+        .columns
+        .iter()
+        .map(|c| escape(c))
+        .collect::<Vec<_>>()
+        .join(",")];
+    for row in &table.rows {
+        lines.push(
+            table
+                .columns
+                .iter()
+                .map(|c| escape(&cell_text(row.get(c))))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    }
+    lines.join("\n")
+}
+
+fn format_markdown(table: &TabularResult) -> String {
+    let escape = |field: &str| field.replace('|', "\\|").replace('\n', " ");
+
+    let mut lines = vec![
+        format!(
+            "| {} |",
+            table
+                .columns
+                .iter()
+                .map(|c| escape(c))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ),
+        format!(
+            "| {} |",
+            table
+                .columns
+                .iter()
+                .map(|_| "---")
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ),
+    ];
+    for row in &table.rows {
+        lines.push(format!(
+            "| {} |",
+            table
+                .columns
+                .iter()
+                .map(|c| escape(&cell_text(row.get(c))))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+    }
+    lines.join("\n")
+}
+
+fn format_plain_table(table: &TabularResult) -> String {
+    let cells: Vec<Vec<String>> = table
+        .rows
+        .iter()
+        .map(|row| {
+            table
+                .columns
+                .iter()
+                .map(|c| cell_text(row.get(c)))
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(c.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let render = |fields: &[String]| {
+        fields
+            .iter()
+            .zip(&widths)
+            .map(|(f, w)| format!("{:<width$}", f, width = *w))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    let mut lines = vec![render(&table.columns), render(&separator)];
+    for row in &cells {
+        lines.push(render(row));
+    }
+    lines.join("\n")
+}
+
+fn format_toml(table: &TabularResult) -> Result<String, String> {
+    // TOML has no null, so missing and null cells are omitted from each row
+    let rows: Vec<toml::Value> = table
+        .rows
+        .iter()
+        .map(|row| {
+            let mut record = toml::map::Map::new();
+            for column in &table.columns {
+                if let Some(value) = row.get(column).and_then(json_to_toml) {
+                    record.insert(column.clone(), value);
+                }
+            }
+            toml::Value::Table(record)
+        })
+        .collect();
+
+    let mut document = toml::map::Map::new();
+    document.insert("rows".to_string(), toml::Value::Array(rows));
+    toml::to_string(&toml::Value::Table(document))
+        .map_err(|e| format!("Failed to serialize TOML: {}", e))
+}
+
+fn json_to_toml(value: &Value) -> Option<toml::Value> {
+    match value {
+        Value::Null => None,
+        Value::Bool(b) => Some(toml::Value::Boolean(*b)),
+        Value::Number(n) => n
+            .as_i64()
+            .map(toml::Value::Integer)
+            .or_else(|| n.as_f64().map(toml::Value::Float)),
+        Value::String(s) => Some(toml::Value::String(s.clone())),
+        Value::Array(items) => Some(toml::Value::Array(
+            items.iter().filter_map(json_to_toml).collect(),
+        )),
+        Value::Object(map) => Some(toml::Value::Table(
+            map.iter()
+                .filter_map(|(k, v)| json_to_toml(v).map(|v| (k.clone(), v)))
+                .collect(),
+        )),
+    }
+}
+
+fn format_yaml(table: &TabularResult) -> String {
+    if table.rows.is_empty() {
+        return "[]".to_string();
+    }
+
+    let mut lines = Vec::new();
+    for row in &table.rows {
+        let mut first = true;
+        for column in &table.columns {
+            let Some(value) = row.get(column) else {
+                continue;
+            };
+            let prefix = if first { "- " } else { "  " };
+            first = false;
+            lines.push(format!(
+                "{}{}: {}",
+                prefix,
+                yaml_string(column),
+                yaml_scalar(value)
+            ));
+        }
+        if first {
+            lines.push("- {}".to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::String(s) => yaml_string(s),
+        // Arrays are written in flow style, which JSON syntax satisfies
+        other => other.to_string(),
+    }
+}
+
+fn yaml_string(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ' '))
+        && !s.ends_with(' ')
+        && !matches!(
+            s.to_lowercase().as_str(),
+            "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "~"
+        );
+
+    if plain {
+        s.to_string()
+    } else {
+        // JSON strings are valid YAML double-quoted scalars
+        serde_json::to_string(s).unwrap_or_else(|_| format!("\"{}\"", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_columns_flatten_nested_objects() {
+        let value = json!([
+            { "id": "branch-1", "position": { "x": 0, "y": 10 } },
+            { "id": "group-1", "label": "Group" }
+        ]);
+
+        let table = to_tabular(&value);
+        assert_eq!(
+            table.columns,
+            vec!["id", "position.x", "position.y", "label"]
+        );
+        assert_eq!(table.rows[1].get("position.x"), None);
+    }
+
+    #[test]
+    fn test_unit_values_split_into_columns() {
+        let value = json!([
+            { "type": "Pipe", "length": "0.5 km" },
+            { "type": "Pipe", "length": 12 }
+        ]);
+
+        let csv = format_query_result_as(&value, TableFormat::Csv).unwrap();
+        assert_eq!(csv, "length,length_unit,type\n0.5,km,Pipe\n12,,Pipe");
+    }
+
+    #[test]
+    fn test_labels_starting_with_a_number_are_not_split() {
+        let value = json!([{ "label": "2 Compressor Station" }]);
+
+        let csv = format_query_result_as(&value, TableFormat::Csv).unwrap();
+        assert_eq!(csv, "label\n2 Compressor Station");
+    }
+
+    #[test]
+    fn test_markdown_and_yaml_output() {
+        let value = json!({ "label": "a|b", "enabled": true });
+
+        let md = format_query_result_as(&value, TableFormat::Markdown).unwrap();
+        assert_eq!(md, "| enabled | label |\n| --- | --- |\n| true | a\\|b |");
+
+        let yaml = format_query_result_as(&value, TableFormat::Yaml).unwrap();
+        assert_eq!(yaml, "- enabled: true\n  label: \"a|b\"");
+    }
+
+    #[test]
+    fn test_toml_output_omits_nulls() {
+        let value = json!([{ "id": "branch-1", "parentId": null }]);
+
+        let toml = format_query_result_as(&value, TableFormat::Toml).unwrap();
+        assert_eq!(toml.trim(), "[[rows]]\nid = \"branch-1\"");
+    }
+}
@@ -352,6 +352,44 @@ mod tests {
         assert_eq!(result.as_f64(), Some(20.0));
     }
 
+    #[test]
+    fn test_execute_scope_resolution_reports_resolve_errors() {
+        use super::super::executor::QueryError;
+        use crate::scope::resolver::ResolveError;
+
+        let mut network = create_test_network();
+        let group = |id: &str, parent: &str| {
+            NodeData::Group(GroupNode {
+                base: NodeBase {
+                    id: id.to_string(),
+                    type_: "labeledGroup".to_string(),
+                    label: None,
+                    position: Position { x: 0.0, y: 0.0 },
+                    parent_id: Some(parent.to_string()),
+                    width: None,
+                    height: None,
+                    extra: std::collections::HashMap::new(),
+                },
+            })
+        };
+        network.nodes.push(group("group-1", "group-2"));
+        network.nodes.push(group("group-2", "group-1"));
+        if let NodeData::Branch(branch) = &mut network.nodes[0] {
+            branch.base.parent_id = Some("group-1".to_string());
+        }
+        let resolver = ScopeResolver::new(Config::empty());
+        let executor = QueryExecutor::with_scope_resolver(&network, &resolver);
+
+        let query = parse_query_path("branch-1/blocks/1/temperature?scope=block,group").unwrap();
+        let err = executor.execute(&query).unwrap_err();
+        assert!(
+            matches!(err, QueryError::Resolve(ResolveError::ParentCycle(_))),
+            "{:?}",
+            err
+        );
+        assert!(!err.to_string().starts_with("Invalid type"), "{}", err);
+    }
+
     #[test]
     fn test_execute_network_query_edges() {
         let network = create_test_network();
@@ -370,11 +408,14 @@ mod tests {
         let network = create_test_network();
         let executor = QueryExecutor::new(&network);
         let query = parse_query_path("nodes[type=branch]").unwrap();
-        let result = executor.execute(&query);
+        let result = executor.execute(&query).unwrap();
 
-        // Network query with filter - verify it executes without error
-        // The actual filtering logic may need adjustment based on node structure
-        assert!(result.is_ok() || result.is_err());
+        let nodes = result.as_array().expect("Expected array of nodes");
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(
+            nodes[0].get("id").and_then(|v| v.as_str()),
+            Some("branch-1")
+        );
     }
 
     #[test]
@@ -0,0 +1,165 @@
+// Schema defaults: property values filled into blocks that don't otherwise have one
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dim::UnitProcessor;
+use crate::parser::models::{Block, Network, NodeData};
+use crate::schema::registry::{PropertyMetadata, SchemaRegistry};
+use crate::scope::materialize::scope_key;
+use crate::scope::resolver::{BlockContext, ScopeResolver};
+use std::collections::HashMap;
+use toml::Value;
+
+/// Provenance recorded under `_<property>_scope` for a value taken from the
+/// schema's `default`; savers leave these properties out
+pub const DEFAULT_PROVENANCE: &str = "default";
+
+/// Whether a block's value for `property` was filled in from the schema default
+pub fn is_schema_default(block: &Block, property: &str) -> bool {
+    block
+        .extra
+        .get(&scope_key(property))
+        .and_then(Value::as_str)
+        == Some(DEFAULT_PROVENANCE)
+}
+
+/// Insert the schema `default` of every property a block doesn't resolve through
+/// its scope chain (its own value, branch, groups, config.toml, upstream), tagged
+/// with `default` provenance. Unit strings are normalized like loaded values
+/// (kept as written on wasm, where the dim library isn't available).
+/// Returns the number of values inserted
+pub fn apply_schema_defaults(
+    network: &mut Network,
+    resolver: &ScopeResolver,
+    registry: &SchemaRegistry,
+    version: &str,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    // Decide against the network as loaded, so a default filled into one block
+    // doesn't look like an upstream value to the blocks after it
+    let mut inserts: Vec<(usize, usize, HashMap<String, Value>)> = Vec::new();
+    for (node_index, node) in network.nodes.iter().enumerate() {
+        let NodeData::Branch(branch) = node else {
+            continue;
+        };
+        for (block_index, block) in branch.blocks.iter().enumerate() {
+            let Some(schema) = registry.get_schema(version, &block.type_) else {
+                continue;
+            };
+            let ctx = BlockContext::in_network(network, branch, block_index)?;
+
+            let mut defaults = HashMap::new();
+            for (property, meta) in &schema.properties {
+                let Some(default) = &meta.default else {
+                    continue;
+                };
+                if resolver.resolve_property(property, &ctx)?.is_none() {
+                    defaults.insert(property.clone(), default.clone());
+                }
+            }
+            if !defaults.is_empty() {
+                let defaults = normalize_units(&defaults, &schema.properties)
+                    .map_err(|e| format!("Invalid default in schema '{}': {}", block.type_, e))?;
+                inserts.push((node_index, block_index, defaults));
+            }
+        }
+    }
+
+    let mut applied = 0;
+    for (node_index, block_index, defaults) in inserts {
+        let NodeData::Branch(branch) = &mut network.nodes[node_index] else {
+            continue;
+        };
+        let block = &mut branch.blocks[block_index];
+        for (key, value) in defaults {
+            if !key.starts_with('_') {
+                block.extra.insert(
+                    scope_key(&key),
+                    Value::String(DEFAULT_PROVENANCE.to_string()),
+                );
+                applied += 1;
+            }
+            block.extra.insert(key, value);
+        }
+    }
+    Ok(applied)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn normalize_units(
+    defaults: &HashMap<String, Value>,
+    properties: &HashMap<String, PropertyMetadata>,
+) -> Result<HashMap<String, Value>, String> {
+    UnitProcessor::new()
+        .process_hashmap_with_schema(defaults, properties)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn normalize_units(
+    defaults: &HashMap<String, Value>,
+    _properties: &HashMap<String, PropertyMetadata>,
+) -> Result<HashMap<String, Value>, String> {
+    Ok(defaults.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::loader::load_network_from_files;
+    use crate::scope::config::Config;
+
+    #[test]
+    fn test_defaults_fill_only_unresolved_properties() {
+        let files = HashMap::from([(
+            "branch-1.toml".to_string(),
+            r#"
+type = "branch"
+position = { x = 0, y = 0 }
+efficiency = 0.8
+
+[[block]]
+type = "CaptureUnit"
+
+[[block]]
+type = "CaptureUnit"
+capture_technology = "membrane"
+"#
+            .to_string(),
+        )]);
+        let (mut network, _) = load_network_from_files(files, None).unwrap();
+
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry
+            .load_library_from_files(
+                "v1.0",
+                HashMap::from([(
+                    "capture.json".to_string(),
+                    r#"{"block_type": "CaptureUnit", "version": "v1.0",
+                        "optional": ["capture_technology", "efficiency", "trains"],
+                        "properties": {
+                            "capture_technology": {"enum": ["amine", "membrane"], "default": "amine"},
+                            "efficiency": {"default": 0.9},
+                            "trains": {"type": "integer"}}}"#
+                        .to_string(),
+                )]),
+            )
+            .unwrap();
+        let resolver = ScopeResolver::new(Config::empty());
+
+        let applied = apply_schema_defaults(&mut network, &resolver, &registry, "v1.0").unwrap();
+        assert_eq!(applied, 1);
+
+        let Some(NodeData::Branch(branch)) = network.nodes.first() else {
+            panic!("Expected Branch node");
+        };
+        let (first, second) = (&branch.blocks[0], &branch.blocks[1]);
+        assert_eq!(
+            first.extra.get("capture_technology"),
+            Some(&Value::String("amine".to_string()))
+        );
+        assert!(is_schema_default(first, "capture_technology"));
+        // The branch's efficiency is inherited, so its default isn't needed
+        assert!(!first.extra.contains_key("efficiency"));
+        assert!(!is_schema_default(second, "capture_technology"));
+        assert!(!second.extra.contains_key("_capture_technology_scope"));
+    }
+}
@@ -0,0 +1,420 @@
+// Draft schema inference from the blocks of existing networks
+
+use crate::dim::{get_dimension, looks_like_unit_string, DimParser};
+use crate::parser::models::{Network, NodeData};
+use crate::schema::registry::{PropertyMetadata, ValueType};
+use crate::scope::materialize::is_metadata_key;
+use std::collections::{BTreeMap, BTreeSet};
+use toml::Value;
+
+/// A draft schema for one block type
+#[derive(Debug)]
+pub struct InferredSchema {
+    pub block_type: String,
+    /// Number of blocks the schema was inferred from
+    pub blocks: usize,
+    /// File name for the schema ("Capture Unit" -> "capture-unit.json"), unique
+    /// among the schemas inferred together
+    pub file_name: String,
+    /// Schema JSON in the format `SchemaRegistry` loads
+    pub schema: serde_json::Value,
+    /// Things worth a look before the draft is used (mixed types, unknown dimensions)
+    pub notes: Vec<String>,
+}
+
+/// File name stem for a block type ("Capture Unit" -> "capture-unit")
+fn slug(block_type: &str) -> String {
+    block_type
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Give each schema its own file. A block type whose name slugs like an earlier
+/// one ("capture-unit" after "Capture Unit") gets the first free numbered name
+fn assign_file_names(schemas: &mut [InferredSchema]) {
+    let natural: BTreeSet<String> = schemas.iter().map(|s| slug(&s.block_type)).collect();
+    // file stem -> block type written there
+    let mut taken: BTreeMap<String, String> = BTreeMap::new();
+    for schema in schemas.iter_mut() {
+        let stem = slug(&schema.block_type);
+        let name = match taken.get(&stem) {
+            Some(owner) => {
+                let name = (2..)
+                    .map(|n| format!("{}-{}", stem, n))
+                    .find(|name| !natural.contains(name) && !taken.contains_key(name))
+                    .unwrap_or_default();
+                schema.notes.push(format!(
+                    "'{}.json' is already the schema of '{}'; written as '{}.json'",
+                    stem, owner, name
+                ));
+                name
+            }
+            None => stem,
+        };
+        schema.file_name = format!("{}.json", name);
+        taken.insert(name, schema.block_type.clone());
+    }
+}
+
+/// What a single block's value for a property looks like
+#[derive(Debug, Clone, PartialEq)]
+enum Observed {
+    /// A quantity written with a unit, as originally written
+    Quantity(String),
+    Integer,
+    Float,
+    String,
+    Boolean,
+    Array,
+    Other,
+}
+
+/// Infer one schema per block type from every block in `networks`. A property set
+/// on every block of a type is required, any other is optional
+pub fn infer_schemas(networks: &[&Network], version: &str) -> Vec<InferredSchema> {
+    // block type -> (block count, property -> observed values)
+    let mut seen: BTreeMap<&str, (usize, BTreeMap<&str, Vec<Observed>>)> = BTreeMap::new();
+    for network in networks {
+        for node in &network.nodes {
+            let NodeData::Branch(branch) = node else {
+                continue;
+            };
+            for block in &branch.blocks {
+                let (count, properties) = seen.entry(block.type_.as_str()).or_default();
+                *count += 1;
+                for (name, value) in &block.extra {
+                    if is_metadata_key(name) {
+                        continue;
+                    }
+                    let original = block
+                        .extra
+                        .get(&format!("_{}_original", name))
+                        .and_then(|v| v.as_str());
+                    properties
+                        .entry(name.as_str())
+                        .or_default()
+                        .push(observe(value, original));
+                }
+            }
+        }
+    }
+
+    let mut parser = DimParser::new().ok();
+    let mut schemas: Vec<InferredSchema> = seen
+        .into_iter()
+        .map(|(block_type, (blocks, properties))| {
+            let mut required = Vec::new();
+            let mut optional = Vec::new();
+            let mut metadata = BTreeMap::new();
+            let mut notes = Vec::new();
+
+            for (name, observed) in properties {
+                if observed.len() == blocks {
+                    required.push(name);
+                } else {
+                    optional.push(name);
+                }
+                let meta = infer_property(name, &observed, parser.as_mut(), &mut notes);
+                metadata.insert(name, meta);
+            }
+
+            let schema = serde_json::json!({
+                "block_type": block_type,
+                "version": version,
+                "required": required,
+                "optional": optional,
+                "properties": metadata,
+            });
+            InferredSchema {
+                block_type: block_type.to_string(),
+                blocks,
+                file_name: String::new(),
+                schema,
+                notes,
+            }
+        })
+        .collect();
+    assign_file_names(&mut schemas);
+    schemas
+}
+
+fn observe(value: &Value, original: Option<&str>) -> Observed {
+    if let Some(original) = original {
+        return Observed::Quantity(original.to_string());
+    }
+    match value {
+        // Unit strings the loader couldn't parse stay strings
+        Value::String(s) if looks_like_unit_string(s) => Observed::Quantity(s.clone()),
+        Value::String(_) => Observed::String,
+        Value::Integer(_) => Observed::Integer,
+        Value::Float(_) => Observed::Float,
+        Value::Boolean(_) => Observed::Boolean,
+        Value::Array(_) => Observed::Array,
+        Value::Datetime(_) | Value::Table(_) => Observed::Other,
+    }
+}
+
+fn infer_property(
+    name: &str,
+    observed: &[Observed],
+    parser: Option<&mut DimParser>,
+    notes: &mut Vec<String>,
+) -> PropertyMetadata {
+    let quantities: Vec<&str> = observed
+        .iter()
+        .filter_map(|o| match o {
+            Observed::Quantity(original) => Some(original.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let kinds: BTreeSet<&str> = observed
+        .iter()
+        .map(|o| match o {
+            // Bare numbers next to unit strings are quantities in base units
+            Observed::Quantity(_) | Observed::Integer | Observed::Float
+                if !quantities.is_empty() =>
+            {
+                "quantity"
+            }
+            Observed::Quantity(_) => "quantity",
+            Observed::Integer => "integer",
+            Observed::Float => "number",
+            Observed::String => "string",
+            Observed::Boolean => "boolean",
+            Observed::Array => "array",
+            Observed::Other => "other",
+        })
+        .collect();
+
+    let mut meta = PropertyMetadata::default();
+    match kinds.iter().copied().collect::<Vec<_>>().as_slice() {
+        ["quantity"] => {
+            meta.dimension = quantity_dimension(name, &quantities, parser, notes);
+            meta.default_unit = most_common_unit(&quantities);
+        }
+        ["integer"] => meta.value_type = Some(ValueType::Integer),
+        ["integer", "number"] | ["number"] => meta.value_type = Some(ValueType::Number),
+        ["string"] => meta.value_type = Some(ValueType::String),
+        ["boolean"] => meta.value_type = Some(ValueType::Boolean),
+        ["array"] => meta.value_type = Some(ValueType::Array),
+        ["other"] => {}
+        kinds => notes.push(format!(
+            "'{}' has values of several types ({}); no type inferred",
+            name,
+            kinds.join(", ")
+        )),
+    }
+    meta
+}
+
+/// The dimension every unit string of a property agrees on
+fn quantity_dimension(
+    name: &str,
+    quantities: &[&str],
+    parser: Option<&mut DimParser>,
+    notes: &mut Vec<String>,
+) -> Option<String> {
+    let Some(parser) = parser else {
+        notes.push(format!(
+            "'{}' holds unit strings, but the unit parser is unavailable; no dimension inferred",
+            name
+        ));
+        return None;
+    };
+
+    let mut dimensions = BTreeSet::new();
+    let mut unknown = Vec::new();
+    for original in quantities {
+        match parser
+            .parse_unit_string(original)
+            .ok()
+            .and_then(|parsed| get_dimension(parser, &parsed))
+        {
+            Some(dimension) => {
+                dimensions.insert(dimension);
+            }
+            None => unknown.push(*original),
+        }
+    }
+
+    if !unknown.is_empty() {
+        notes.push(format!(
+            "'{}': no known dimension for {}",
+            name,
+            unknown
+                .iter()
+                .map(|u| format!("'{}'", u))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if dimensions.len() > 1 {
+        notes.push(format!(
+            "'{}' mixes dimensions ({}); no dimension inferred",
+            name,
+            dimensions.iter().cloned().collect::<Vec<_>>().join(", ")
+        ));
+        return None;
+    }
+    dimensions.into_iter().next()
+}
+
+/// Unit written most often in a property's unit strings ("100 bar" -> "bar")
+fn most_common_unit(quantities: &[&str]) -> Option<String> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for quantity in quantities {
+        let unit = quantity
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || "+-.eE".contains(c))
+            .trim();
+        if !unit.is_empty() {
+            *counts.entry(unit).or_default() += 1;
+        }
+    }
+    // Ties go to the unit that sorts first, so the output is stable
+    let best = counts.values().copied().max()?;
+    counts
+        .into_iter()
+        .find(|(_, count)| *count == best)
+        .map(|(unit, _)| unit.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::loader::load_network_from_files;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_infers_required_optional_and_types() {
+        let files = HashMap::from([(
+            "branch-1.toml".to_string(),
+            r#"
+type = "branch"
+position = { x = 0, y = 0 }
+
+[[block]]
+type = "Capture Unit"
+trains = 2
+efficiency = 0.9
+technology = "amine"
+
+[[block]]
+type = "Capture Unit"
+trains = 3
+efficiency = 1
+insulated = true
+technology = 4
+"#
+            .to_string(),
+        )]);
+        let (network, _) = load_network_from_files(files, None).unwrap();
+
+        let schemas = infer_schemas(&[&network], "v1.0");
+        assert_eq!(schemas.len(), 1);
+        let capture = &schemas[0];
+        assert_eq!(capture.file_name, "capture-unit.json");
+        assert_eq!(capture.blocks, 2);
+        assert_eq!(
+            capture.schema["required"],
+            serde_json::json!(["efficiency", "technology", "trains"])
+        );
+        assert_eq!(capture.schema["optional"], serde_json::json!(["insulated"]));
+
+        let properties = &capture.schema["properties"];
+        assert_eq!(properties["trains"]["type"], "integer");
+        assert_eq!(properties["efficiency"]["type"], "number");
+        assert_eq!(properties["insulated"]["type"], "boolean");
+        assert!(properties["technology"].get("type").is_none());
+        assert!(capture.notes[0].contains("'technology'"));
+    }
+
+    #[test]
+    fn test_similar_block_types_get_their_own_files() {
+        let files = HashMap::from([(
+            "branch-1.toml".to_string(),
+            r#"
+type = "branch"
+position = { x = 0, y = 0 }
+
+[[block]]
+type = "Capture Unit"
+
+[[block]]
+type = "capture-unit"
+
+[[block]]
+type = "capture-unit-2"
+"#
+            .to_string(),
+        )]);
+        let (network, _) = load_network_from_files(files, None).unwrap();
+
+        let schemas = infer_schemas(&[&network], "v1.0");
+        let names: Vec<(&str, &str)> = schemas
+            .iter()
+            .map(|s| (s.block_type.as_str(), s.file_name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Capture Unit", "capture-unit.json"),
+                ("capture-unit", "capture-unit-3.json"),
+                ("capture-unit-2", "capture-unit-2.json"),
+            ]
+        );
+        assert!(schemas[1].notes[0].contains("'Capture Unit'"));
+    }
+
+    #[test]
+    fn test_infers_dimension_from_unit_strings() {
+        let files = HashMap::from([(
+            "branch-1.toml".to_string(),
+            r#"
+type = "branch"
+position = { x = 0, y = 0 }
+
+[[block]]
+type = "Compressor"
+pressure = "100 bar"
+length = "2 m"
+
+[[block]]
+type = "Compressor"
+pressure = "1.5 MPa"
+length = "3 s"
+
+[[block]]
+type = "Compressor"
+pressure = "80 bar"
+"#
+            .to_string(),
+        )]);
+        let (network, _) = load_network_from_files(files, None).unwrap();
+
+        let schemas = infer_schemas(&[&network], "v1.0");
+        let properties = &schemas[0].schema["properties"];
+        assert_eq!(properties["pressure"]["dimension"], "pressure");
+        assert_eq!(properties["pressure"]["defaultUnit"], "bar");
+        assert!(properties["length"].get("dimension").is_none());
+        assert!(schemas[0].notes[0].contains("mixes dimensions"));
+    }
+
+    #[test]
+    fn test_most_common_unit() {
+        assert_eq!(
+            most_common_unit(&["100 bar", "1e5 Pa", "2.5 bar"]).as_deref(),
+            Some("bar")
+        );
+        assert_eq!(most_common_unit(&[]), None);
+    }
+}
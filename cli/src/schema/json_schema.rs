@@ -0,0 +1,339 @@
+// JSON Schema export: describes network TOML files for editors (e.g. taplo)
+
+use crate::schema::registry::{
+    Limit, PropertyMetadata, SchemaDefinition, SchemaRegistry, ValueType,
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+const DRAFT: &str = "http://json-schema.org/draft-07/schema#";
+
+/// Node types a network file can declare with `type = "..."`
+pub const NODE_TYPES: [&str; 5] = [
+    "branch",
+    "labeledGroup",
+    "geographicAnchor",
+    "geographicWindow",
+    "image",
+];
+
+/// One JSON Schema document per node type (node type -> document). `[[block]]`
+/// entries in branches are a union over the version's block types, picked by `type`
+pub fn export_json_schemas(
+    registry: &SchemaRegistry,
+    version: &str,
+) -> Result<BTreeMap<&'static str, Value>, String> {
+    let library = registry.library(version)?;
+    let mut block_types = registry.list_block_types(version);
+    block_types.sort();
+
+    let blocks: Map<String, Value> = block_types
+        .iter()
+        .map(|block_type| {
+            (
+                definition_name(block_type),
+                block_schema(&library.schemas[*block_type]),
+            )
+        })
+        .collect();
+
+    Ok(NODE_TYPES
+        .iter()
+        .map(|&node_type| {
+            let mut document = node_schema(node_type);
+            document["$schema"] = json!(DRAFT);
+            document["title"] = json!(format!("{} node (schema {})", node_type, version));
+            if node_type == "branch" {
+                document["properties"]["block"] = json!({
+                    "type": "array",
+                    "description": "Blocks in flow order",
+                    "items": block_union(&block_types),
+                });
+                document["definitions"] = json!({ "blocks": blocks });
+            }
+            (node_type, document)
+        })
+        .collect())
+}
+
+/// Fields every node has, plus the ones specific to `node_type`
+fn node_schema(node_type: &str) -> Value {
+    let mut properties = json!({
+        "type": { "const": node_type },
+        "label": { "type": "string", "description": "Display name" },
+        "parentId": { "type": "string", "description": "ID of the group containing this node" },
+        "position": {
+            "type": "object",
+            "required": ["x", "y"],
+            "properties": {
+                "x": { "type": "number" },
+                "y": { "type": "number" },
+            },
+        },
+        "width": { "type": "integer", "minimum": 0 },
+        "height": { "type": "integer", "minimum": 0 },
+    });
+    let mut required = vec!["type", "position"];
+
+    match node_type {
+        "branch" => {
+            properties["outgoing"] = json!({
+                "type": "array",
+                "description": "Edges to downstream branches",
+                "items": {
+                    "type": "object",
+                    "required": ["target", "weight"],
+                    "properties": {
+                        "target": { "type": "string", "description": "ID of the downstream branch" },
+                        "weight": { "type": "integer", "minimum": 0 },
+                    },
+                },
+            });
+        }
+        "image" => {
+            properties["path"] = json!({
+                "type": "string",
+                "description": "Image path relative to the network directory",
+            });
+            required.push("path");
+        }
+        _ => {}
+    }
+
+    json!({
+        "type": "object",
+        "required": required,
+        "properties": properties,
+    })
+}
+
+/// `[[block]]` items: `type` must name a block type, and selects the schema the
+/// rest of the block is checked against
+fn block_union(block_types: &[&String]) -> Value {
+    let cases: Vec<Value> = block_types
+        .iter()
+        .map(|block_type| {
+            json!({
+                "if": {
+                    "required": ["type"],
+                    "properties": { "type": { "const": block_type } },
+                },
+                "then": { "$ref": format!("#/definitions/blocks/{}", definition_name(block_type)) },
+            })
+        })
+        .collect();
+
+    json!({
+        "type": "object",
+        "required": ["type"],
+        "properties": {
+            "type": { "enum": block_types, "description": "Block type" },
+            "quantity": { "type": "integer", "minimum": 1, "default": 1 },
+        },
+        "allOf": cases,
+    })
+}
+
+fn block_schema(schema: &SchemaDefinition) -> Value {
+    let mut properties = Map::new();
+    properties.insert("type".into(), json!({ "const": schema.block_type }));
+
+    let mut names: Vec<&String> = schema
+        .required_properties
+        .iter()
+        .chain(&schema.optional_properties)
+        .chain(schema.properties.keys())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let meta = schema.properties.get(name).cloned().unwrap_or_default();
+        let mut property = property_schema(&meta);
+        // Required properties may be inherited from the branch, a group or
+        // config.toml, so the block itself isn't required to set them
+        if schema.required_properties.contains(name) {
+            append_description(&mut property, "Required (may be inherited)".to_string());
+        }
+        properties.insert(name.clone(), property);
+    }
+
+    let mut block = json!({
+        "title": schema.block_type,
+        "type": "object",
+        "properties": properties,
+    });
+    if let Some(description) = &schema.description {
+        block["description"] = json!(description);
+    }
+    block
+}
+
+fn property_schema(meta: &PropertyMetadata) -> Value {
+    let mut property = Map::new();
+
+    match meta.value_type {
+        Some(ValueType::Array) => {
+            property.insert("type".into(), json!("array"));
+            if let Some(items) = &meta.items {
+                property.insert("items".into(), property_schema(items));
+            }
+        }
+        // Quantities may be written with a unit ("100 bar") or as a bare number,
+        // whatever type the schema gives the number
+        _ if meta.dimension.is_some() => {
+            property.insert("type".into(), json!(["number", "string"]));
+        }
+        Some(value_type) => {
+            property.insert("type".into(), json!(value_type.as_str()));
+        }
+        None => {}
+    }
+
+    if let Some(title) = &meta.title {
+        property.insert("title".into(), json!(title));
+    }
+    // Numeric limits are in base SI units and apply to bare numbers; limits with
+    // a unit can't be expressed, so they are only described
+    let mut range = Vec::new();
+    if let Some(min) = &meta.min {
+        match min {
+            Limit::Number(n) => {
+                property.insert("minimum".into(), json!(n));
+            }
+            Limit::Quantity(_) => range.push(format!("at least {}", min)),
+        }
+    }
+    if let Some(max) = &meta.max {
+        match max {
+            Limit::Number(n) => {
+                property.insert("maximum".into(), json!(n));
+            }
+            Limit::Quantity(_) => range.push(format!("at most {}", max)),
+        }
+    }
+    if let Some(values) = &meta.allowed_values {
+        property.insert("enum".into(), json!(values));
+    }
+    if let Some(pattern) = &meta.pattern {
+        // Schema patterns must match in full; JSON Schema patterns match anywhere
+        property.insert("pattern".into(), json!(format!("^(?:{})$", pattern)));
+    }
+    if let Some(default) = &meta.default {
+        property.insert("default".into(), json!(default));
+    }
+
+    let mut property = Value::Object(property);
+    if let Some(description) = &meta.description {
+        append_description(&mut property, description.clone());
+    }
+    if let Some(dimension) = &meta.dimension {
+        append_description(
+            &mut property,
+            match &meta.default_unit {
+                Some(unit) => format!("Dimension: {} (default unit {})", dimension, unit),
+                None => format!("Dimension: {}", dimension),
+            },
+        );
+    }
+    if !range.is_empty() {
+        append_description(&mut property, format!("Range: {}", range.join(", ")));
+    }
+    property
+}
+
+fn append_description(property: &mut Value, line: String) {
+    let description = match property.get("description").and_then(Value::as_str) {
+        Some(existing) => format!("{}\n{}", existing, line),
+        None => line,
+    };
+    property["description"] = json!(description);
+}
+
+/// Block types can contain spaces ("Capture Unit"); keep definition keys to
+/// characters that need no escaping in a `$ref`
+fn definition_name(block_type: &str) -> String {
+    block_type
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_blocks_are_a_union_on_type() {
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry
+            .load_library_from_files(
+                "v1.0",
+                HashMap::from([
+                    (
+                        "equipment.json".to_string(),
+                        r#"{"block_type": "Equipment", "version": "v1.0", "abstract": true,
+                            "properties": {"capex": {"type": "number", "min": 0}}}"#
+                            .to_string(),
+                    ),
+                    (
+                        "capture.json".to_string(),
+                        r#"{"block_type": "Capture Unit", "version": "v1.0", "extends": "Equipment",
+                            "description": "Post-combustion capture plant",
+                            "required": ["pressure"], "optional": ["technology", "length"],
+                            "properties": {
+                                "length": {"type": "number", "dimension": "length", "min": 0},
+                                "pressure": {"dimension": "pressure", "defaultUnit": "bar",
+                                             "max": "200 bar", "title": "Pressure"},
+                                "technology": {"type": "string", "enum": ["amine", "membrane"]}}}"#
+                            .to_string(),
+                    ),
+                ]),
+            )
+            .unwrap();
+
+        let documents = export_json_schemas(&registry, "v1.0").unwrap();
+        assert_eq!(documents.len(), NODE_TYPES.len());
+        assert!(documents["image"]["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("path")));
+
+        let branch = &documents["branch"];
+        let items = &branch["properties"]["block"]["items"];
+        assert_eq!(items["properties"]["type"]["enum"], json!(["Capture Unit"]));
+        assert_eq!(
+            items["allOf"][0]["then"]["$ref"],
+            json!("#/definitions/blocks/Capture_Unit")
+        );
+
+        let capture = &branch["definitions"]["blocks"]["Capture_Unit"];
+        assert_eq!(
+            capture["description"],
+            json!("Post-combustion capture plant")
+        );
+        assert_eq!(capture["properties"]["capex"]["minimum"], json!(0.0));
+        assert_eq!(
+            capture["properties"]["technology"]["enum"],
+            json!(["amine", "membrane"])
+        );
+        let pressure = &capture["properties"]["pressure"];
+        assert_eq!(pressure["type"], json!(["number", "string"]));
+        assert_eq!(pressure["title"], json!("Pressure"));
+        assert!(pressure.get("maximum").is_none());
+        let length = &capture["properties"]["length"];
+        assert_eq!(length["type"], json!(["number", "string"]));
+        assert_eq!(length["minimum"], json!(0.0));
+        assert_eq!(
+            pressure["description"],
+            json!("Dimension: pressure (default unit bar)\nRange: at most 200 bar\nRequired (may be inherited)")
+        );
+    }
+}
@@ -0,0 +1,1066 @@
+// Declarative migrations between schema library versions, applied to network TOML files
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dim::DimParser;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml::de::{DeTable, DeValue};
+use toml::{Spanned, Value};
+
+/// Subdirectory of a version's schema directory holding migrations into that version
+pub const MIGRATIONS_DIR: &str = "migrations";
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Steps that move a network from one schema version to the next
+/// (`<schemas-dir>/<to>/migrations/<from>.json`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Migration {
+    pub from: String,
+    pub to: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub operations: Vec<Operation>,
+}
+
+/// One rewrite applied to every matching block. `blockType` limits an operation
+/// to blocks of that type; without it every block is considered. Property
+/// renames and unit changes also reach the properties blocks inherit (node
+/// properties, config.toml `[properties]` and `[blockDefaults.<Type>]`; renames
+/// also `[dimensions]` and `[inheritance.rules]`), and type changes the
+/// `blockDefaults` and `unitPreferences` tables and rule `overrides` of a type
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum Operation {
+    /// Rename a block property
+    RenameProperty {
+        #[serde(default)]
+        block_type: Option<String>,
+        from: String,
+        to: String,
+    },
+    /// Rewrite a property as a quantity in another unit. Bare numbers are read in
+    /// `from`; `factor`, when given, replaces unit conversion (for dimension changes)
+    ChangeUnit {
+        #[serde(default)]
+        block_type: Option<String>,
+        property: String,
+        #[serde(default)]
+        from: Option<String>,
+        to: String,
+        #[serde(default)]
+        factor: Option<f64>,
+    },
+    /// Give blocks of one type a new type, chosen by the first case whose `when`
+    /// properties all match (a case without `when` always matches)
+    SplitBlockType { from: String, into: Vec<SplitCase> },
+    /// Give blocks of several types one type, optionally recording the old type
+    MergeBlockTypes {
+        from: Vec<String>,
+        into: String,
+        #[serde(default)]
+        tag_property: Option<String>,
+    },
+    /// Set a property on blocks that don't have it
+    SetDefault {
+        #[serde(default)]
+        block_type: Option<String>,
+        property: String,
+        value: Value,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SplitCase {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub when: BTreeMap<String, Value>,
+}
+
+impl Operation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::RenameProperty { .. } => "renameProperty",
+            Operation::ChangeUnit { .. } => "changeUnit",
+            Operation::SplitBlockType { .. } => "splitBlockType",
+            Operation::MergeBlockTypes { .. } => "mergeBlockTypes",
+            Operation::SetDefault { .. } => "setDefault",
+        }
+    }
+}
+
+/// Load the migrations in a version's schema directory, if it has any
+pub fn load_migrations(version_dir: &Path) -> Result<Vec<Migration>, Box<dyn std::error::Error>> {
+    let dir = version_dir.join(MIGRATIONS_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let content = std::fs::read_to_string(path)?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid migration {}: {}", path.display(), e).into())
+        })
+        .collect()
+}
+
+/// One change (or skipped change) made to a block or table
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationChange {
+    pub file: String,
+    /// "branch-4/blocks/0", "branch-4" for node properties, or the config key
+    /// for config.toml
+    pub location: String,
+    pub op: String,
+    pub message: String,
+}
+
+/// Result of migrating a network directory; nothing is written until `write`
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub changes: Vec<MigrationChange>,
+    /// Places an operation applied to but couldn't rewrite
+    pub warnings: Vec<MigrationChange>,
+    /// New content of every file that changed
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+impl MigrationReport {
+    pub fn write(&self) -> std::io::Result<()> {
+        for (path, content) in &self.files {
+            std::fs::write(path, content)?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts a number from one unit to another: (value, from, to) -> value
+pub type UnitConversion<'c> = dyn FnMut(f64, &str, &str) -> Result<f64, String> + 'c;
+
+/// Apply `migrations` in order to config.toml and every node file of a network
+/// directory, and move a `schemaVersion` pinned at the first migration's version
+/// to the last's.
+/// Units are converted with the dim library
+#[cfg(not(target_arch = "wasm32"))]
+pub fn migrate_directory(
+    directory: &Path,
+    migrations: &[&Migration],
+) -> Result<MigrationReport, Box<dyn std::error::Error>> {
+    let mut parser = DimParser::new().ok();
+    migrate_directory_with(directory, migrations, &mut |value, from, to| {
+        parser
+            .as_mut()
+            .ok_or("unit conversion is not available")?
+            .convert_to_unit(value, from, to)
+            .map_err(|e| e.to_string())
+    })
+}
+
+/// Like `migrate_directory`, converting units with `convert_unit` (operations
+/// with a `factor` don't need it)
+pub fn migrate_directory_with(
+    directory: &Path,
+    migrations: &[&Migration],
+    convert_unit: &mut UnitConversion,
+) -> Result<MigrationReport, Box<dyn std::error::Error>> {
+    let mut report = MigrationReport::default();
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("toml"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let file = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let original = std::fs::read_to_string(&path)?;
+
+        let mut content = original.clone();
+        for migration in migrations {
+            for operation in &migration.operations {
+                content = apply_operation(&content, &file, operation, convert_unit, &mut report)
+                    .map_err(|e| format!("{}: {}", file, e))?;
+            }
+        }
+        if file == CONFIG_FILE {
+            // Layers may be shared with other networks, so only this file is rewritten
+            let values: Option<toml::Table> = toml::from_str(&original).ok();
+            if let Some(base) = values
+                .as_ref()
+                .and_then(|v| v.get("extends"))
+                .and_then(|v| v.as_str())
+            {
+                report.warnings.push(MigrationChange {
+                    file: file.clone(),
+                    location: "extends".to_string(),
+                    op: "extends".to_string(),
+                    message: format!("'{}' and the layers below it are not migrated", base),
+                });
+            }
+            if let (Some(first), Some(last)) = (migrations.first(), migrations.last()) {
+                content =
+                    repin_schema_version(&content, &file, &first.from, &last.to, &mut report)?;
+            }
+        }
+
+        if content != original {
+            report.files.insert(path, content);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Text replacements in one file, applied back to front so spans stay valid
+struct Edits {
+    edits: Vec<(Range<usize>, String)>,
+}
+
+impl Edits {
+    fn apply(mut self, content: &str) -> String {
+        let mut content = content.to_string();
+        self.edits
+            .sort_by_key(|edit| std::cmp::Reverse(edit.0.start));
+        for (range, text) in self.edits {
+            content.replace_range(range, &text);
+        }
+        content
+    }
+}
+
+fn apply_operation(
+    content: &str,
+    file: &str,
+    operation: &Operation,
+    convert_unit: &mut UnitConversion,
+    report: &mut MigrationReport,
+) -> Result<String, Box<dyn std::error::Error>> {
+    // Values come from a regular parse, positions from the spanned one
+    let values: toml::Table = toml::from_str(content)?;
+    let document = DeTable::parse(content)?;
+    let document = document.get_ref();
+    let mut rewrite = Rewrite {
+        content,
+        file,
+        operation,
+        convert_unit,
+        report,
+        edits: Edits { edits: Vec::new() },
+    };
+
+    if file == CONFIG_FILE {
+        if let Some((properties, table)) = sub_table(&values, document, "properties") {
+            rewrite.properties(properties, table, "properties", None);
+        }
+        if let Some((defaults, tables)) = sub_table(&values, document, "blockDefaults") {
+            rewrite.type_tables(defaults, tables, "blockDefaults");
+            for block_type in defaults.keys() {
+                if let Some((properties, table)) = sub_table(defaults, tables, block_type) {
+                    let location = format!("blockDefaults.{}", block_type);
+                    rewrite.properties(properties, table, &location, Some(block_type));
+                }
+            }
+        }
+        if let Some((preferences, tables)) = sub_table(&values, document, "unitPreferences") {
+            rewrite.preferences(preferences, tables, "unitPreferences");
+        }
+        if let Some((inheritance, tables)) = sub_table(&values, document, "inheritance") {
+            if let Some((rules, tables)) = sub_table(inheritance, tables, "rules") {
+                rewrite.rules(rules, tables);
+            }
+        }
+        if matches!(operation, Operation::RenameProperty { .. }) {
+            if let Some((dimensions, table)) = sub_table(&values, document, "dimensions") {
+                rewrite.properties(dimensions, table, "dimensions", None);
+            }
+        }
+    } else {
+        let node = Path::new(file)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(file)
+            .to_string();
+        rewrite.properties(&values, document, &node, None);
+        if let Some((preferences, tables)) = sub_table(&values, document, "unitPreferences") {
+            rewrite.preferences(preferences, tables, &format!("{}/unitPreferences", node));
+        }
+        if values.get("type").and_then(|v| v.as_str()) == Some("branch") {
+            rewrite.blocks(&values, document, &node);
+        }
+    }
+
+    Ok(rewrite.edits.apply(content))
+}
+
+/// The edits and report entries one operation makes to one file
+struct Rewrite<'a, 'c> {
+    content: &'a str,
+    file: &'a str,
+    operation: &'a Operation,
+    convert_unit: &'a mut UnitConversion<'c>,
+    report: &'a mut MigrationReport,
+    edits: Edits,
+}
+
+impl Rewrite<'_, '_> {
+    fn record(&mut self, location: &str, warning: bool, message: String) {
+        let change = MigrationChange {
+            file: self.file.to_string(),
+            location: location.to_string(),
+            op: self.operation.name().to_string(),
+            message,
+        };
+        if warning {
+            self.report.warnings.push(change);
+        } else {
+            self.report.changes.push(change);
+        }
+    }
+
+    /// Rename or convert a property of a block or `[blockDefaults.<Type>]` table
+    /// (`block_type` given), or of a table every block type inherits from: node
+    /// properties and config `[properties]`. Operations limited to one block type
+    /// leave the latter alone
+    fn properties(
+        &mut self,
+        values: &toml::Table,
+        table: &DeTable,
+        location: &str,
+        block_type: Option<&str>,
+    ) {
+        let operation = self.operation;
+        let (filter, property) = match operation {
+            Operation::RenameProperty {
+                block_type, from, ..
+            } => (block_type, from),
+            Operation::ChangeUnit {
+                block_type,
+                property,
+                ..
+            } => (block_type, property),
+            _ => return,
+        };
+        let (Some(value), Some((key, spanned))) =
+            (values.get(property), table.get_key_value(property.as_str()))
+        else {
+            return;
+        };
+        match (filter.as_deref(), block_type) {
+            (Some(filter), Some(block_type)) if filter != block_type => return,
+            (Some(filter), None) => {
+                self.record(
+                    location,
+                    true,
+                    format!(
+                        "'{}' left as is: it applies to every block type, not only {}",
+                        property, filter
+                    ),
+                );
+                return;
+            }
+            _ => {}
+        }
+
+        match operation {
+            Operation::RenameProperty { from, to, .. } => {
+                if values.contains_key(to) {
+                    self.record(
+                        location,
+                        true,
+                        format!("'{}' not renamed: it already has '{}'", from, to),
+                    );
+                    return;
+                }
+                if !written_once(self.content, spanned) {
+                    self.record(
+                        location,
+                        true,
+                        format!(
+                            "'{}' not renamed: it is written across several tables or dotted keys",
+                            from
+                        ),
+                    );
+                    return;
+                }
+                self.edits.edits.push((key.span(), toml_key(to)));
+                self.record(location, false, format!("renamed '{}' to '{}'", from, to));
+            }
+            Operation::ChangeUnit {
+                from, to, factor, ..
+            } => match convert(value, from.as_deref(), to, *factor, self.convert_unit) {
+                Ok(converted) => {
+                    let text = Value::String(converted.clone()).to_string();
+                    self.edits.edits.push((spanned.span(), text));
+                    self.record(
+                        location,
+                        false,
+                        format!("{} = {} -> \"{}\"", property, value, converted),
+                    );
+                }
+                Err(reason) => self.record(
+                    location,
+                    true,
+                    format!(
+                        "{} = {} not converted to {}: {}",
+                        property, value, to, reason
+                    ),
+                ),
+            },
+            _ => {}
+        }
+    }
+
+    /// Follow a split or merge in a table keyed by block type (`blockDefaults`,
+    /// `unitPreferences`). A table is renamed when its blocks all get one new type
+    /// and nothing is there yet; otherwise it is reported
+    fn type_tables(&mut self, values: &toml::Table, tables: &DeTable, location: &str) {
+        let renames: Vec<(&String, Result<&str, String>)> = match self.operation {
+            Operation::SplitBlockType { from, into } => {
+                let mut types: Vec<&str> = into.iter().map(|case| case.type_.as_str()).collect();
+                types.sort();
+                types.dedup();
+                match types.as_slice() {
+                    [only] => vec![(from, Ok(*only))],
+                    _ => vec![(from, Err(format!("its blocks become {}", types.join(", "))))],
+                }
+            }
+            Operation::MergeBlockTypes { from, into, .. } => {
+                let present: Vec<&String> = from
+                    .iter()
+                    .filter(|t| values.contains_key(t.as_str()))
+                    .collect();
+                let target = match present.len() {
+                    1 => Ok(into.as_str()),
+                    n => Err(format!("{} of the merged types have one", n)),
+                };
+                present.into_iter().map(|t| (t, target.clone())).collect()
+            }
+            _ => return,
+        };
+
+        for (from, target) in renames {
+            let Some((key, value)) = tables.get_key_value(from.as_str()) else {
+                continue;
+            };
+            let at = format!("{}.{}", location, from);
+            // A table built from dotted keys ("Pipe.roughness = 1") names the type on every line
+            let dotted = !matches!(self.content[value.span()].chars().next(), Some('[' | '{'));
+            let target = target.and_then(|to| {
+                if values.contains_key(to) {
+                    Err(format!("'{}' already has one", to))
+                } else if dotted {
+                    Err("it is written with dotted keys".to_string())
+                } else {
+                    Ok(to)
+                }
+            });
+            match target {
+                Ok(to) => {
+                    self.edits.edits.push((key.span(), toml_key(to)));
+                    self.record(&at, false, format!("'{}' -> '{}'", from, to));
+                }
+                Err(reason) => self.record(
+                    &at,
+                    true,
+                    format!("not moved to the new block type: {}", reason),
+                ),
+            }
+        }
+    }
+
+    /// A `unitPreferences` table: display units keyed by block type, then property
+    fn preferences(&mut self, values: &toml::Table, tables: &DeTable, location: &str) {
+        self.type_tables(values, tables, location);
+        if !matches!(self.operation, Operation::RenameProperty { .. }) {
+            return;
+        }
+        for block_type in values.keys().filter(|key| *key != "dimensions") {
+            if let Some((properties, table)) = sub_table(values, tables, block_type) {
+                let at = format!("{}.{}", location, block_type);
+                self.properties(properties, table, &at, Some(block_type));
+            }
+        }
+    }
+
+    /// `[inheritance.rules]`: rules keyed by property, each with `overrides` keyed
+    /// by block type. Like `[properties]`, a rule applies to every block type
+    fn rules(&mut self, values: &toml::Table, tables: &DeTable) {
+        if matches!(self.operation, Operation::RenameProperty { .. }) {
+            self.properties(values, tables, "inheritance.rules", None);
+        }
+        for property in values.keys() {
+            if let Some((rule, table)) = sub_table(values, tables, property) {
+                if let Some((overrides, tables)) = sub_table(rule, table, "overrides") {
+                    let location = format!("inheritance.rules.{}.overrides", property);
+                    self.type_tables(overrides, tables, &location);
+                }
+            }
+        }
+    }
+
+    fn blocks(&mut self, values: &toml::Table, document: &DeTable, node: &str) {
+        let (Some(Value::Array(blocks)), Some(spanned)) =
+            (values.get("block"), document.get("block"))
+        else {
+            return;
+        };
+        let DeValue::Array(spanned) = spanned.get_ref() else {
+            return;
+        };
+
+        let operation = self.operation;
+        for (index, (block, spanned)) in blocks.iter().zip(spanned.iter()).enumerate() {
+            let (Some(block), DeValue::Table(table)) = (block.as_table(), spanned.get_ref()) else {
+                continue;
+            };
+            let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("");
+            let location = format!("{}/blocks/{}", node, index);
+
+            match operation {
+                Operation::RenameProperty { .. } | Operation::ChangeUnit { .. } => {
+                    self.properties(block, table, &location, Some(block_type));
+                }
+                Operation::SplitBlockType { from, into } => {
+                    if block_type != from {
+                        continue;
+                    }
+                    let case = into.iter().find(|case| {
+                        case.when.iter().all(|(key, expected)| {
+                            block.get(key).is_some_and(|v| same_value(v, expected))
+                        })
+                    });
+                    match case {
+                        Some(case) => {
+                            set_type(&mut self.edits, table, &case.type_);
+                            self.record(
+                                &location,
+                                false,
+                                format!("type '{}' -> '{}'", from, case.type_),
+                            );
+                        }
+                        None => self.record(
+                            &location,
+                            true,
+                            format!("no case of the '{}' split matches this block", from),
+                        ),
+                    }
+                }
+                Operation::MergeBlockTypes {
+                    from,
+                    into,
+                    tag_property,
+                } => {
+                    if !from.iter().any(|t| t == block_type) {
+                        continue;
+                    }
+                    set_type(&mut self.edits, table, into);
+                    let mut message = format!("type '{}' -> '{}'", block_type, into);
+                    if let Some(tag) = tag_property {
+                        if !block.contains_key(tag) {
+                            insert_property(
+                                &mut self.edits,
+                                self.content,
+                                spanned,
+                                table,
+                                tag,
+                                &Value::String(block_type.to_string()),
+                            );
+                            message.push_str(&format!(", {} = \"{}\"", tag, block_type));
+                        }
+                    }
+                    self.record(&location, false, message);
+                }
+                Operation::SetDefault {
+                    block_type: filter,
+                    property,
+                    value,
+                } => {
+                    if filter.as_deref().is_some_and(|t| t != block_type)
+                        || block.contains_key(property)
+                    {
+                        continue;
+                    }
+                    insert_property(
+                        &mut self.edits,
+                        self.content,
+                        spanned,
+                        table,
+                        property,
+                        value,
+                    );
+                    self.record(&location, false, format!("set {} = {}", property, value));
+                }
+            }
+        }
+    }
+}
+
+/// A table of the document, with its values and spans
+fn sub_table<'t, 'i>(
+    values: &'t toml::Table,
+    document: &'t DeTable<'i>,
+    key: &str,
+) -> Option<(&'t toml::Table, &'t DeTable<'i>)> {
+    match (values.get(key), document.get(key).map(|v| v.get_ref())) {
+        (Some(Value::Table(values)), Some(DeValue::Table(table))) => Some((values, table)),
+        _ => None,
+    }
+}
+
+/// Whether renaming a value's key renames all of it: a table written with dotted
+/// keys, or with `[...]` headers of its own below it, names the key more than once
+fn written_once(content: &str, value: &Spanned<DeValue>) -> bool {
+    match content[value.span()].chars().next() {
+        Some('{') => true,
+        Some('[') => !has_header_tables(content, value),
+        _ => !matches!(value.get_ref(), DeValue::Table(_)),
+    }
+}
+
+fn has_header_tables(content: &str, value: &Spanned<DeValue>) -> bool {
+    let DeValue::Table(table) = value.get_ref() else {
+        return false;
+    };
+    table.values().any(|child| {
+        matches!(child.get_ref(), DeValue::Table(_))
+            && (content[child.span()].starts_with('[') || has_header_tables(content, child))
+    })
+}
+
+/// Point a pinned `schemaVersion = from` at `to`
+fn repin_schema_version(
+    content: &str,
+    file: &str,
+    from: &str,
+    to: &str,
+    report: &mut MigrationReport,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let document = DeTable::parse(content)?;
+    let Some(version) = document.get_ref().get("schemaVersion") else {
+        return Ok(content.to_string());
+    };
+    if version.get_ref().as_str() != Some(from) {
+        return Ok(content.to_string());
+    }
+
+    report.changes.push(MigrationChange {
+        file: file.to_string(),
+        location: "schemaVersion".to_string(),
+        op: "schemaVersion".to_string(),
+        message: format!("'{}' -> '{}'", from, to),
+    });
+    Ok(Edits {
+        edits: vec![(version.span(), Value::String(to.to_string()).to_string())],
+    }
+    .apply(content))
+}
+
+fn set_type(edits: &mut Edits, table: &DeTable, block_type: &str) {
+    if let Some(current) = table.get("type") {
+        edits.edits.push((
+            current.span(),
+            Value::String(block_type.to_string()).to_string(),
+        ));
+    }
+}
+
+/// Add `key = value` to a block: on the line after its `type` in a `[[block]]`
+/// table, or at the end of an inline table
+fn insert_property(
+    edits: &mut Edits,
+    content: &str,
+    block: &Spanned<DeValue>,
+    table: &DeTable,
+    key: &str,
+    value: &Value,
+) {
+    let entry = format!("{} = {}", toml_key(key), value);
+    let span = block.span();
+
+    if content[span.clone()].starts_with('{') {
+        let close = span.end - 1;
+        let separator = if table.is_empty() { " " } else { ", " };
+        edits
+            .edits
+            .push((close..close, format!("{}{} ", separator, entry)));
+        return;
+    }
+
+    let after = table
+        .get("type")
+        .map(|t| t.span().end)
+        .unwrap_or(span.start);
+    let line_end = content[after..]
+        .find('\n')
+        .map(|i| after + i + 1)
+        .unwrap_or(content.len());
+    let prefix = if line_end == content.len() && !content.ends_with('\n') {
+        "\n"
+    } else {
+        ""
+    };
+    edits
+        .edits
+        .push((line_end..line_end, format!("{}{}\n", prefix, entry)));
+}
+
+/// A key as written in TOML, quoted unless it is a bare key
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+/// A value as a quantity string in `to`: "<number> <to>"
+fn convert(
+    value: &Value,
+    from: Option<&str>,
+    to: &str,
+    factor: Option<f64>,
+    convert_unit: &mut UnitConversion,
+) -> Result<String, String> {
+    let (number, unit) = match value {
+        Value::Integer(n) => (*n as f64, from.map(|u| u.to_string())),
+        Value::Float(n) => (*n, from.map(|u| u.to_string())),
+        Value::String(s) => {
+            let s = s.trim();
+            let split = s.find(char::is_whitespace).unwrap_or(s.len());
+            let number: f64 = s[..split]
+                .parse()
+                .map_err(|_| format!("'{}' is not a number followed by a unit", s))?;
+            let unit = s[split..].trim();
+            (
+                number,
+                (!unit.is_empty())
+                    .then(|| unit.to_string())
+                    .or(from.map(|u| u.to_string())),
+            )
+        }
+        other => return Err(format!("a {} can't carry a unit", other.type_str())),
+    };
+
+    let converted = match factor {
+        Some(factor) => number * factor,
+        None => {
+            let unit =
+                unit.ok_or("the value has no unit and the migration gives no 'from' unit")?;
+            convert_unit(number, &unit, to)?
+        }
+    };
+
+    Ok(format!("{} {}", format_number(converted), to))
+}
+
+/// A number without floating-point noise ("1.2000000000000002" -> "1.2")
+fn format_number(value: f64) -> String {
+    let text = format!("{:.10}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Integer(x), Value::Float(y)) | (Value::Float(y), Value::Integer(x)) => {
+            *x as f64 == *y
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(operations: &str) -> Migration {
+        serde_json::from_str(&format!(
+            r#"{{"from": "v1.0", "to": "v1.1", "operations": {}}}"#,
+            operations
+        ))
+        .unwrap()
+    }
+
+    fn migrate(content: &str, migration: &Migration) -> (String, MigrationReport) {
+        let mut report = MigrationReport::default();
+        let mut content = content.to_string();
+        let mut no_conversion = |_: f64, _: &str, _: &str| Err("no unit conversion".to_string());
+        for operation in &migration.operations {
+            content = apply_operation(
+                &content,
+                "branch-1.toml",
+                operation,
+                &mut no_conversion,
+                &mut report,
+            )
+            .unwrap();
+        }
+        (content, report)
+    }
+
+    #[test]
+    fn test_operations_rewrite_blocks_in_place() {
+        let content = r#"type = "branch"
+label = "Branch 1"
+
+# Compression stage
+[[block]]
+type = "Compressor"
+len = 10 # metres
+kind = "electric"
+
+[[block]]
+type = "Compressor"
+kind = "gas"
+
+[[block]]
+type = "Pump"
+head = "3.5 m"
+"#;
+        let migration = migration(
+            r#"[
+            {"op": "renameProperty", "blockType": "Compressor", "from": "len", "to": "length"},
+            {"op": "splitBlockType", "from": "Compressor", "into": [
+                {"type": "ElectricCompressor", "when": {"kind": "electric"}},
+                {"type": "GasCompressor"}
+            ]},
+            {"op": "mergeBlockTypes", "from": ["Pump"], "into": "RotatingEquipment", "tagProperty": "kind"},
+            {"op": "setDefault", "blockType": "GasCompressor", "property": "efficiency", "value": 0.8},
+            {"op": "changeUnit", "property": "head", "to": "cm", "factor": 100}
+        ]"#,
+        );
+
+        let (migrated, report) = migrate(content, &migration);
+        assert_eq!(
+            migrated,
+            r#"type = "branch"
+label = "Branch 1"
+
+# Compression stage
+[[block]]
+type = "ElectricCompressor"
+length = 10 # metres
+kind = "electric"
+
+[[block]]
+type = "GasCompressor"
+efficiency = 0.8
+kind = "gas"
+
+[[block]]
+type = "RotatingEquipment"
+kind = "Pump"
+head = "350 cm"
+"#
+        );
+        assert_eq!(report.changes.len(), 6);
+        assert!(report.warnings.is_empty());
+        assert_eq!(report.changes[0].location, "branch-1/blocks/0");
+    }
+
+    #[test]
+    fn test_unconvertible_values_are_reported() {
+        let content = "type = \"branch\"\n\n[[block]]\ntype = \"Pipe\"\nlength = 10\nwidth = 2\n";
+        let migration = migration(
+            r#"[
+            {"op": "changeUnit", "property": "length", "to": "km"},
+            {"op": "renameProperty", "from": "width", "to": "length"}
+        ]"#,
+        );
+
+        let (migrated, report) = migrate(content, &migration);
+        assert_eq!(migrated, content);
+        assert_eq!(report.warnings.len(), 2);
+        assert!(report.warnings[0].message.contains("no 'from' unit"));
+        assert!(report.warnings[1].message.contains("already has 'length'"));
+    }
+
+    #[test]
+    fn test_migrate_directory_reaches_inherited_properties() {
+        let dir = std::env::temp_dir().join(format!("dagger-migrate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = r#"schemaVersion = "v1.0"
+
+[properties]
+len = 5
+
+[blockDefaults.Compressor]
+len = 8
+head = 2
+
+[unitPreferences.Compressor]
+len = "km"
+
+[unitPreferences.Pump]
+head = "ft"
+"#;
+        let group = "type = \"labeledGroup\"\nlen = 3\nhead = 1\n";
+        let branch = "type = \"branch\"\nparentId = \"group-1\"\n\n[[block]]\ntype = \"Compressor\"\nlen = 10\n";
+        std::fs::write(dir.join("config.toml"), config).unwrap();
+        std::fs::write(dir.join("group-1.toml"), group).unwrap();
+        std::fs::write(dir.join("branch-1.toml"), branch).unwrap();
+
+        let migration = migration(
+            r#"[
+            {"op": "renameProperty", "from": "len", "to": "length"},
+            {"op": "changeUnit", "blockType": "Compressor", "property": "head", "to": "cm", "factor": 100},
+            {"op": "mergeBlockTypes", "from": ["Compressor", "Pump"], "into": "Rotating"}
+        ]"#,
+        );
+        let mut no_conversion = |_: f64, _: &str, _: &str| Err("no unit conversion".to_string());
+        let report = migrate_directory_with(&dir, &[&migration], &mut no_conversion).unwrap();
+
+        assert_eq!(
+            report.files[&dir.join("config.toml")],
+            r#"schemaVersion = "v1.1"
+
+[properties]
+length = 5
+
+[blockDefaults.Rotating]
+length = 8
+head = "200 cm"
+
+[unitPreferences.Compressor]
+length = "km"
+
+[unitPreferences.Pump]
+head = "ft"
+"#
+        );
+        assert_eq!(
+            report.files[&dir.join("group-1.toml")],
+            "type = \"labeledGroup\"\nlength = 3\nhead = 1\n"
+        );
+        assert_eq!(
+            report.files[&dir.join("branch-1.toml")],
+            "type = \"branch\"\nparentId = \"group-1\"\n\n[[block]]\ntype = \"Rotating\"\nlength = 10\n"
+        );
+
+        // The group's head is inherited by every block type, and two preference
+        // tables can't both become Rotating
+        let warnings: Vec<&str> = report
+            .warnings
+            .iter()
+            .map(|w| w.location.as_str())
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "unitPreferences.Compressor",
+                "unitPreferences.Pump",
+                "group-1"
+            ]
+        );
+
+        // Nothing is written until asked
+        assert_eq!(
+            std::fs::read_to_string(dir.join("config.toml")).unwrap(),
+            config
+        );
+        report.write().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("branch-1.toml")).unwrap(),
+            report.files[&dir.join("branch-1.toml")]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_migrate_directory_renames_rules_and_dimensions() {
+        let dir = std::env::temp_dir().join(format!("dagger-migrate-rules-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = r#"extends = "../shared/config.toml"
+
+[dimensions]
+len = "length"
+flow = "volumetricFlow"
+
+[inheritance.rules]
+len = ["block", "branch", "global"]
+flow = { inheritance = ["block", "global"], overrides = { Compressor = ["block"] } }
+
+[inheritance.rules.costing]
+inheritance = ["block", "global"]
+
+[inheritance.rules.costing.overrides]
+Compressor = ["group", "global"]
+"#;
+        std::fs::write(dir.join("config.toml"), config).unwrap();
+
+        let migration = migration(
+            r#"[
+            {"op": "renameProperty", "from": "len", "to": "length"},
+            {"op": "renameProperty", "blockType": "Pipe", "from": "flow", "to": "rate"},
+            {"op": "renameProperty", "from": "costing", "to": "cost"},
+            {"op": "splitBlockType", "from": "Compressor", "into": [{"type": "GasCompressor"}]}
+        ]"#,
+        );
+        let mut no_conversion = |_: f64, _: &str, _: &str| Err("no unit conversion".to_string());
+        let report = migrate_directory_with(&dir, &[&migration], &mut no_conversion).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            report.files[&dir.join("config.toml")],
+            r#"extends = "../shared/config.toml"
+
+[dimensions]
+length = "length"
+flow = "volumetricFlow"
+
+[inheritance.rules]
+length = ["block", "branch", "global"]
+flow = { inheritance = ["block", "global"], overrides = { GasCompressor = ["block"] } }
+
+[inheritance.rules.costing]
+inheritance = ["block", "global"]
+
+[inheritance.rules.costing.overrides]
+GasCompressor = ["group", "global"]
+"#
+        );
+
+        // The flow rule and dimension apply to every block type, the costing rule
+        // is spread over two tables, and the base config is someone else's
+        let warnings: Vec<(&str, &str)> = report
+            .warnings
+            .iter()
+            .map(|w| (w.location.as_str(), w.op.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                ("inheritance.rules", "renameProperty"),
+                ("dimensions", "renameProperty"),
+                ("inheritance.rules", "renameProperty"),
+                ("extends", "extends"),
+            ]
+        );
+        assert!(report.warnings[2].message.contains("several tables"));
+    }
+}
@@ -1,3 +1,9 @@
+pub mod defaults;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod infer;
+pub mod json_schema;
 pub mod loader;
+pub mod migration;
 pub mod registry;
+pub mod topology;
 pub mod validator;
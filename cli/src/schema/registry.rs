@@ -1,50 +1,179 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use crate::schema::migration::{load_migrations, Migration};
+use std::cell::OnceCell;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct SchemaLibrary {
     pub version: String,
     pub schemas: HashMap<String, SchemaDefinition>, // block_type -> schema
+    /// Migrations into this version, from `<version>/migrations/*.json`
+    pub migrations: Vec<Migration>,
 }
 
 #[derive(Debug, Clone)]
 pub struct SchemaDefinition {
     pub block_type: String,
     pub version: String,
+    /// What the block type represents
+    pub description: Option<String>,
+    /// Schema this one extends; its properties are already merged in
+    pub extends: Option<String>,
+    /// Only a base for other schemas; blocks can't use this type
+    pub is_abstract: bool,
+    /// Where blocks of this type may appear
+    pub topology: TopologyConstraints,
     pub required_properties: Vec<String>,
     pub optional_properties: Vec<String>,
     /// Property metadata: property name -> PropertyMetadata
     pub properties: HashMap<String, PropertyMetadata>,
 }
 
-#[derive(Debug, Clone)]
+/// Serializes with the field names schema files use
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct PropertyMetadata {
     /// Dimension type (e.g., "pressure", "length", "temperature")
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dimension: Option<String>,
     /// Default unit for display (e.g., "bar", "m", "C")
+    #[serde(rename = "defaultUnit", skip_serializing_if = "Option::is_none")]
     pub default_unit: Option<String>,
     /// Display name/title for the property
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
+    /// Longer explanation of the property
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Expected value type
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub value_type: Option<ValueType>,
     /// Minimum value constraint
-    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<Limit>,
     /// Maximum value constraint
-    pub max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<Limit>,
+    /// Allowed values
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub allowed_values: Option<Vec<toml::Value>>,
+    /// Regular expression string values must match in full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Schema for each element of an array value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<PropertyMetadata>>,
+    /// Value used when a block doesn't set the property
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<toml::Value>,
+}
+
+/// Where a block type may appear in a network
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopologyConstraints {
+    /// Must be the first or the last block of its branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<BlockPosition>,
+    /// Block types (or bases they extend) allowed directly before it: the previous
+    /// block in the branch, or the last block of each branch feeding into it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follows: Option<Vec<String>>,
+    /// The branch holding it must have no incoming edges
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_incoming_edges: bool,
+}
+
+impl TopologyConstraints {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fill the constraints this schema leaves unset from `base`
+    fn over(self, base: &TopologyConstraints) -> Self {
+        Self {
+            position: self.position.or(base.position),
+            follows: self.follows.or_else(|| base.follows.clone()),
+            no_incoming_edges: self.no_incoming_edges || base.no_incoming_edges,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockPosition {
+    First,
+    Last,
+}
+
+/// Type a property value must have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    Number,
+    /// A number without a fractional part
+    Integer,
+    String,
+    Boolean,
+    Array,
+}
+
+impl ValueType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::Number => "number",
+            ValueType::Integer => "integer",
+            ValueType::String => "string",
+            ValueType::Boolean => "boolean",
+            ValueType::Array => "array",
+        }
+    }
+}
+
+/// A `min`/`max` bound: a number in base SI units, or a quantity with its own unit
+/// ("10 bar") that is normalized before comparing
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum Limit {
+    Number(f64),
+    Quantity(String),
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Number(n) => write!(f, "{}", n),
+            Limit::Quantity(q) => write!(f, "{}", q),
+        }
+    }
 }
 
 pub struct SchemaRegistry {
-    libraries: HashMap<String, SchemaLibrary>, // version -> library
+    /// Every known version: directories found under `schemas_dir` start out empty
+    /// and are read the first time a schema from them is asked for
+    libraries: HashMap<String, OnceCell<Result<SchemaLibrary, String>>>, // version -> library
     schemas_dir: PathBuf,
 }
 
 impl SchemaRegistry {
     pub fn new(schemas_dir: PathBuf) -> Self {
+        let libraries = discover_versions(&schemas_dir)
+            .into_iter()
+            .map(|version| (version, OnceCell::new()))
+            .collect();
         Self {
-            libraries: HashMap::new(),
+            libraries,
             schemas_dir,
         }
     }
 
     pub fn load_library(&mut self, version: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let library = self.read_library(version)?;
+        self.libraries
+            .insert(version.to_string(), OnceCell::from(Ok(library)));
+        Ok(())
+    }
+
+    fn read_library(&self, version: &str) -> Result<SchemaLibrary, Box<dyn std::error::Error>> {
         let version_dir = self.schemas_dir.join(version);
 
         if !version_dir.exists() {
@@ -56,7 +185,7 @@ impl SchemaRegistry {
             .into());
         }
 
-        let mut schemas = HashMap::new();
+        let mut schemas = Vec::new();
 
         // Scan for schema files
         // We support JSON files (generated from Zod schemas via generate-schemas.ts)
@@ -70,21 +199,15 @@ impl SchemaRegistry {
             if path.extension().and_then(|s| s.to_str()) == Some("json") {
                 // Load schema definition from JSON (generated from Zod)
                 let content = std::fs::read_to_string(&path)?;
-                let schema_json: SchemaJson = serde_json::from_str(&content)?;
-                let schema: SchemaDefinition = schema_json.into();
-                schemas.insert(schema.block_type.clone(), schema);
+                let schema_json: SchemaJson = serde_json::from_str(&content)
+                    .map_err(|e| format!("Invalid schema {}: {}", path.display(), e))?;
+                schemas.push(schema_json.into());
             }
         }
 
-        self.libraries.insert(
-            version.to_string(),
-            SchemaLibrary {
-                version: version.to_string(),
-                schemas,
-            },
-        );
-
-        Ok(())
+        let mut library = build_library(version, schemas)?;
+        library.migrations = load_migrations(&version_dir)?;
+        Ok(library)
     }
 
     /// Load schema library from file contents (filename -> content map)
@@ -94,49 +217,264 @@ impl SchemaRegistry {
         version: &str,
         files: HashMap<String, String>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut schemas = HashMap::new();
+        let mut schemas = Vec::new();
 
         // Process each schema file
         for (_filename, content) in files {
             let schema_json: SchemaJson = serde_json::from_str(&content)?;
-            let schema: SchemaDefinition = schema_json.into();
-            schemas.insert(schema.block_type.clone(), schema);
+            schemas.push(schema_json.into());
         }
 
-        self.libraries.insert(
-            version.to_string(),
-            SchemaLibrary {
-                version: version.to_string(),
-                schemas,
-            },
-        );
-
+        let library = build_library(version, schemas)?;
+        self.libraries
+            .insert(version.to_string(), OnceCell::from(Ok(library)));
         Ok(())
     }
 
+    /// The library for a version, reading it from disk on first use. A version that
+    /// fails to load keeps failing with the same error until `load_library` is called
+    pub fn library(&self, version: &str) -> Result<&SchemaLibrary, String> {
+        let cell = self.libraries.get(version).ok_or_else(|| {
+            format!(
+                "Schema library version '{}' not found in {}",
+                version,
+                self.schemas_dir.display()
+            )
+        })?;
+        cell.get_or_init(|| self.read_library(version).map_err(|e| e.to_string()))
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    /// Schema for a block type, with everything it inherits through `extends`
     pub fn get_schema(&self, version: &str, block_type: &str) -> Option<&SchemaDefinition> {
-        self.libraries
-            .get(version)
+        self.library(version)
+            .ok()
             .and_then(|lib| lib.schemas.get(block_type))
     }
 
+    /// Versions loaded so far, sorted
     pub fn list_versions(&self) -> Vec<&String> {
-        self.libraries.keys().collect()
+        let mut versions: Vec<&String> = self
+            .libraries
+            .iter()
+            .filter(|(_, cell)| matches!(cell.get(), Some(Ok(_))))
+            .map(|(version, _)| version)
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    /// Every version found under the schemas directory or loaded from files, sorted
+    pub fn available_versions(&self) -> Vec<&String> {
+        let mut versions: Vec<&String> = self.libraries.keys().collect();
+        versions.sort();
+        versions
     }
 
+    /// Shortest chain of migrations leading from one version to another, looking
+    /// through every available version that loads
+    pub fn migration_path(&self, from: &str, to: &str) -> Result<Vec<&Migration>, String> {
+        let migrations: Vec<&Migration> = self
+            .libraries
+            .keys()
+            .filter_map(|version| self.library(version).ok())
+            .flat_map(|lib| lib.migrations.iter())
+            .collect();
+
+        // Breadth-first over versions, remembering the migration that reached each
+        let mut reached: HashMap<&str, Option<&Migration>> = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(version) = queue.pop_front() {
+            if version == to {
+                let mut path = Vec::new();
+                let mut current = to;
+                while let Some(Some(migration)) = reached.get(current) {
+                    path.push(*migration);
+                    current = migration.from.as_str();
+                }
+                path.reverse();
+                return Ok(path);
+            }
+            for migration in migrations.iter().filter(|m| m.from == version) {
+                if !reached.contains_key(migration.to.as_str()) {
+                    reached.insert(migration.to.as_str(), Some(migration));
+                    queue.push_back(migration.to.as_str());
+                }
+            }
+        }
+
+        Err(format!("No migration path from '{}' to '{}'", from, to))
+    }
+
+    /// Whether a block type is `ancestor` or extends it, directly or through a base
+    pub fn is_a(&self, version: &str, block_type: &str, ancestor: &str) -> bool {
+        // Cycles are rejected when a library loads, so the walk ends
+        let mut current = Some(block_type);
+        while let Some(name) = current {
+            if name == ancestor {
+                return true;
+            }
+            current = self
+                .get_schema(version, name)
+                .and_then(|schema| schema.extends.as_deref());
+        }
+        false
+    }
+
+    /// Block types blocks can use (abstract base schemas are left out)
     pub fn list_block_types(&self, version: &str) -> Vec<&String> {
-        self.libraries
-            .get(version)
-            .map(|lib| lib.schemas.keys().collect())
+        self.library(version)
+            .map(|lib| {
+                lib.schemas
+                    .values()
+                    .filter(|schema| !schema.is_abstract)
+                    .map(|schema| &schema.block_type)
+                    .collect()
+            })
             .unwrap_or_default()
     }
 }
 
+/// Flatten `extends` chains into the library for `version`
+fn build_library(version: &str, schemas: Vec<SchemaDefinition>) -> Result<SchemaLibrary, String> {
+    let declared: HashMap<String, SchemaDefinition> = schemas
+        .into_iter()
+        .map(|schema| (schema.block_type.clone(), schema))
+        .collect();
+
+    let schemas = declared
+        .keys()
+        .map(|block_type| Ok((block_type.clone(), flatten(&declared, block_type)?)))
+        .collect::<Result<_, String>>()?;
+
+    Ok(SchemaLibrary {
+        version: version.to_string(),
+        schemas,
+        migrations: Vec::new(),
+    })
+}
+
+/// Version directories under `schemas_dir`, sorted; empty when it can't be read
+pub fn discover_versions(schemas_dir: &Path) -> Vec<String> {
+    let mut versions: Vec<String> = std::fs::read_dir(schemas_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    versions.sort();
+    versions
+}
+
+/// A schema merged with its `extends` chain: the base's properties first, each
+/// overridden field by field by the schemas extending it, and required/optional
+/// lists unioned (a property required anywhere in the chain is required)
+fn flatten(
+    schemas: &HashMap<String, SchemaDefinition>,
+    block_type: &str,
+) -> Result<SchemaDefinition, String> {
+    // Walk from the schema to its root base
+    let mut chain: Vec<&SchemaDefinition> = Vec::new();
+    let mut next = Some(block_type);
+    while let Some(name) = next {
+        if let Some(start) = chain.iter().position(|s| s.block_type == name) {
+            let mut cycle: Vec<&str> = chain[start..]
+                .iter()
+                .map(|s| s.block_type.as_str())
+                .collect();
+            cycle.push(name);
+            return Err(format!("Schema extends cycle: {}", cycle.join(" -> ")));
+        }
+
+        let schema = schemas.get(name).ok_or_else(|| match chain.last() {
+            Some(child) => format!(
+                "Schema '{}' extends '{}', which is not in this version",
+                child.block_type, name
+            ),
+            None => format!("Unknown schema '{}'", name),
+        })?;
+        chain.push(schema);
+        next = schema.extends.as_deref();
+    }
+
+    let own = chain[0];
+    let mut flat = SchemaDefinition {
+        block_type: own.block_type.clone(),
+        version: own.version.clone(),
+        description: own.description.clone(),
+        extends: own.extends.clone(),
+        is_abstract: own.is_abstract,
+        topology: TopologyConstraints::default(),
+        required_properties: Vec::new(),
+        optional_properties: Vec::new(),
+        properties: HashMap::new(),
+    };
+
+    for schema in chain.iter().rev() {
+        for name in &schema.required_properties {
+            if !flat.required_properties.contains(name) {
+                flat.required_properties.push(name.clone());
+            }
+        }
+        for name in &schema.optional_properties {
+            if !flat.optional_properties.contains(name) {
+                flat.optional_properties.push(name.clone());
+            }
+        }
+        flat.topology = schema.topology.clone().over(&flat.topology);
+        for (name, meta) in &schema.properties {
+            let merged = match flat.properties.get(name) {
+                Some(base) => meta.clone().over(base),
+                None => meta.clone(),
+            };
+            flat.properties.insert(name.clone(), merged);
+        }
+    }
+    let required = flat.required_properties.clone();
+    flat.optional_properties
+        .retain(|name| !required.contains(name));
+
+    Ok(flat)
+}
+
+impl PropertyMetadata {
+    /// Fill the fields this metadata leaves unset from `base`
+    fn over(self, base: &PropertyMetadata) -> Self {
+        Self {
+            dimension: self.dimension.or_else(|| base.dimension.clone()),
+            default_unit: self.default_unit.or_else(|| base.default_unit.clone()),
+            title: self.title.or_else(|| base.title.clone()),
+            description: self.description.or_else(|| base.description.clone()),
+            value_type: self.value_type.or(base.value_type),
+            min: self.min.or_else(|| base.min.clone()),
+            max: self.max.or_else(|| base.max.clone()),
+            allowed_values: self.allowed_values.or_else(|| base.allowed_values.clone()),
+            pattern: self.pattern.or_else(|| base.pattern.clone()),
+            items: self.items.or_else(|| base.items.clone()),
+            default: self.default.or_else(|| base.default.clone()),
+        }
+    }
+}
+
 // JSON format for schema definitions (simpler than parsing TypeScript/Zod)
+// Every field beyond block_type/version is optional, so older files still load
 #[derive(Debug, Clone, serde::Deserialize)]
 struct SchemaJson {
     block_type: String,
     version: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    extends: Option<String>,
+    #[serde(rename = "abstract", default)]
+    is_abstract: bool,
+    #[serde(default)]
+    topology: TopologyConstraints,
+    #[serde(default)]
     required: Vec<String>,
     #[serde(default)]
     optional: Vec<String>,
@@ -153,9 +491,39 @@ struct PropertyMetadataJson {
     #[serde(default)]
     title: Option<String>,
     #[serde(default)]
-    min: Option<f64>,
+    description: Option<String>,
+    #[serde(rename = "type", default)]
+    value_type: Option<ValueType>,
     #[serde(default)]
-    max: Option<f64>,
+    min: Option<Limit>,
+    #[serde(default)]
+    max: Option<Limit>,
+    #[serde(rename = "enum", default)]
+    allowed_values: Option<Vec<toml::Value>>,
+    #[serde(default)]
+    pattern: Option<String>,
+    #[serde(default)]
+    items: Option<Box<PropertyMetadataJson>>,
+    #[serde(default)]
+    default: Option<toml::Value>,
+}
+
+impl From<PropertyMetadataJson> for PropertyMetadata {
+    fn from(meta: PropertyMetadataJson) -> Self {
+        Self {
+            dimension: meta.dimension,
+            default_unit: meta.default_unit,
+            title: meta.title,
+            description: meta.description,
+            value_type: meta.value_type,
+            min: meta.min,
+            max: meta.max,
+            allowed_values: meta.allowed_values,
+            pattern: meta.pattern,
+            items: meta.items.map(|items| Box::new((*items).into())),
+            default: meta.default,
+        }
+    }
 }
 
 impl From<SchemaJson> for SchemaDefinition {
@@ -163,26 +531,123 @@ impl From<SchemaJson> for SchemaDefinition {
         let properties = json
             .properties
             .into_iter()
-            .map(|(name, meta)| {
-                (
-                    name,
-                    PropertyMetadata {
-                        dimension: meta.dimension,
-                        default_unit: meta.default_unit,
-                        title: meta.title,
-                        min: meta.min,
-                        max: meta.max,
-                    },
-                )
-            })
+            .map(|(name, meta)| (name, meta.into()))
             .collect();
 
         Self {
             block_type: json.block_type,
             version: json.version,
+            description: json.description,
+            extends: json.extends,
+            is_abstract: json.is_abstract,
+            topology: json.topology,
             required_properties: json.required,
             optional_properties: json.optional,
             properties,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(files: &[&str]) -> Result<SchemaRegistry, Box<dyn std::error::Error>> {
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry.load_library_from_files(
+            "v1.0",
+            files
+                .iter()
+                .enumerate()
+                .map(|(i, content)| (format!("{}.json", i), content.to_string()))
+                .collect(),
+        )?;
+        Ok(registry)
+    }
+
+    #[test]
+    fn test_extends_flattens_base_schemas() {
+        let registry = library(&[
+            r#"{"block_type": "Equipment", "version": "v1.0", "abstract": true,
+                "required": ["quantity"], "optional": ["capex"],
+                "properties": {"capex": {"type": "number", "min": 0, "title": "Capital cost"}}}"#,
+            r#"{"block_type": "RotatingEquipment", "version": "v1.0", "abstract": true,
+                "extends": "Equipment", "optional": ["power"],
+                "properties": {"power": {"dimension": "power", "defaultUnit": "kW"}}}"#,
+            r#"{"block_type": "Compressor", "version": "v1.0", "extends": "RotatingEquipment",
+                "required": ["power"],
+                "properties": {"power": {"max": "50 MW"}}}"#,
+        ])
+        .unwrap();
+
+        let compressor = registry.get_schema("v1.0", "Compressor").unwrap();
+        assert_eq!(compressor.extends.as_deref(), Some("RotatingEquipment"));
+        assert!(!compressor.is_abstract);
+        assert_eq!(compressor.required_properties, vec!["quantity", "power"]);
+        assert_eq!(compressor.optional_properties, vec!["capex"]);
+
+        let power = &compressor.properties["power"];
+        assert_eq!(power.dimension.as_deref(), Some("power"));
+        assert_eq!(power.default_unit.as_deref(), Some("kW"));
+        assert_eq!(power.max, Some(Limit::Quantity("50 MW".to_string())));
+        assert_eq!(
+            compressor.properties["capex"].title.as_deref(),
+            Some("Capital cost")
+        );
+
+        assert!(
+            registry
+                .get_schema("v1.0", "Equipment")
+                .unwrap()
+                .is_abstract
+        );
+        assert_eq!(registry.list_block_types("v1.0"), vec!["Compressor"]);
+    }
+
+    #[test]
+    fn test_extends_cycles_and_missing_bases_are_errors() {
+        let err = library(&[
+            r#"{"block_type": "A", "version": "v1.0", "extends": "B"}"#,
+            r#"{"block_type": "B", "version": "v1.0", "extends": "A"}"#,
+        ])
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(err.contains("extends cycle"), "{}", err);
+
+        let err = library(&[r#"{"block_type": "Pump", "version": "v1.0", "extends": "Rotating"}"#])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("'Rotating'"), "{}", err);
+    }
+
+    #[test]
+    fn test_versions_on_disk_load_on_first_use() {
+        let dir = std::env::temp_dir().join(format!("dagger-discover-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("v1.0")).unwrap();
+        std::fs::create_dir_all(dir.join("v2.0")).unwrap();
+        std::fs::write(
+            dir.join("v1.0/pipe.json"),
+            r#"{"block_type": "Pipe", "version": "v1.0"}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("v2.0/broken.json"), "{").unwrap();
+
+        let registry = SchemaRegistry::new(dir.clone());
+        assert_eq!(registry.available_versions(), vec!["v1.0", "v2.0"]);
+        assert!(registry.list_versions().is_empty());
+
+        assert!(registry.get_schema("v1.0", "Pipe").is_some());
+        assert_eq!(registry.list_versions(), vec!["v1.0"]);
+
+        assert!(registry.get_schema("v2.0", "Pipe").is_none());
+        assert!(registry
+            .library("v2.0")
+            .unwrap_err()
+            .contains("broken.json"));
+        assert!(registry.library("v3.0").is_err());
+        assert_eq!(registry.list_versions(), vec!["v1.0"]);
+    }
+}
@@ -0,0 +1,222 @@
+// Topology checks: where a block sits in its branch and in the edge graph
+
+use crate::parser::models::{BranchNode, Network, NodeData};
+use crate::schema::registry::{BlockPosition, SchemaDefinition, SchemaRegistry};
+use crate::schema::validator::ValidationResult;
+
+/// Check a block's schema topology constraints against its place in the network
+pub(crate) fn check_topology(
+    registry: &SchemaRegistry,
+    version: &str,
+    schema: &SchemaDefinition,
+    network: &Network,
+    branch: &BranchNode,
+    index: usize,
+    result: &mut ValidationResult,
+) {
+    let topology = &schema.topology;
+    let block_type = &schema.block_type;
+
+    match topology.position {
+        Some(BlockPosition::First) if index != 0 => result.add_placement(
+            "not-first-in-branch",
+            format!(
+                "'{}' must be the first block of its branch, but is block {} of {}",
+                block_type,
+                index + 1,
+                branch.blocks.len()
+            ),
+            "position first".to_string(),
+        ),
+        Some(BlockPosition::Last) if index + 1 != branch.blocks.len() => result.add_placement(
+            "not-last-in-branch",
+            format!(
+                "'{}' must be the last block of its branch, but is block {} of {}",
+                block_type,
+                index + 1,
+                branch.blocks.len()
+            ),
+            "position last".to_string(),
+        ),
+        _ => {}
+    }
+
+    if let Some(allowed) = &topology.follows {
+        for (location, predecessor) in predecessors(network, branch, index) {
+            let fits = allowed
+                .iter()
+                .any(|ancestor| registry.is_a(version, predecessor, ancestor));
+            if !fits {
+                result.add_placement(
+                    "invalid-predecessor",
+                    format!(
+                        "'{}' may only follow {}, but follows '{}' ({})",
+                        block_type,
+                        allowed.join(", "),
+                        predecessor,
+                        location
+                    ),
+                    format!("follows {}", allowed.join(", ")),
+                );
+            }
+        }
+    }
+
+    if topology.no_incoming_edges {
+        let sources: Vec<&str> = network
+            .edges
+            .iter()
+            .filter(|e| e.target == branch.base.id)
+            .map(|e| e.source.as_str())
+            .collect();
+        if !sources.is_empty() {
+            result.add_placement(
+                "unexpected-incoming-edge",
+                format!(
+                    "'{}' must be in a branch with no incoming edges, but '{}' is fed by {}",
+                    block_type,
+                    branch.base.id,
+                    sources.join(", ")
+                ),
+                "noIncomingEdges".to_string(),
+            );
+        }
+    }
+}
+
+/// Types of the blocks directly before `branch.blocks[index]`, with their locations:
+/// the previous block in the branch, or else the last block of each feeding branch
+fn predecessors<'n>(
+    network: &'n Network,
+    branch: &'n BranchNode,
+    index: usize,
+) -> Vec<(String, &'n str)> {
+    if index > 0 {
+        return vec![(
+            format!("{}/blocks/{}", branch.base.id, index - 1),
+            branch.blocks[index - 1].type_.as_str(),
+        )];
+    }
+
+    network
+        .edges
+        .iter()
+        .filter(|e| e.target == branch.base.id)
+        .filter_map(|e| match network.find_node(&e.source) {
+            Some(NodeData::Branch(source)) => source.blocks.last().map(|block| {
+                (
+                    format!("{}/blocks/{}", source.base.id, source.blocks.len() - 1),
+                    block.type_.as_str(),
+                )
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::models::*;
+    use crate::schema::registry::SchemaRegistry;
+    use crate::schema::validator::SchemaValidator;
+    use crate::scope::resolver::BlockContext;
+    use std::collections::HashMap;
+
+    fn branch(id: &str, outgoing: &[&str], types: &[&str]) -> BranchNode {
+        BranchNode {
+            base: NodeBase {
+                id: id.to_string(),
+                type_: "branch".to_string(),
+                label: None,
+                position: Position { x: 0.0, y: 0.0 },
+                parent_id: None,
+                width: None,
+                height: None,
+                extra: HashMap::new(),
+            },
+            outgoing: outgoing
+                .iter()
+                .map(|target| Outgoing {
+                    target: target.to_string(),
+                    weight: 1,
+                })
+                .collect(),
+            blocks: types
+                .iter()
+                .map(|t| Block {
+                    quantity: Some(1),
+                    type_: t.to_string(),
+                    extra: HashMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_position_predecessor_and_incoming_edge_constraints() {
+        let schemas = [
+            r#"{"block_type": "Equipment", "version": "v1.0", "abstract": true}"#,
+            r#"{"block_type": "Compressor", "version": "v1.0", "extends": "Equipment"}"#,
+            r#"{"block_type": "Source", "version": "v1.0",
+                "topology": {"position": "first", "noIncomingEdges": true}}"#,
+            r#"{"block_type": "Pipe", "version": "v1.0",
+                "topology": {"follows": ["Equipment", "Pipe"]}}"#,
+            r#"{"block_type": "Sink", "version": "v1.0",
+                "topology": {"position": "last", "follows": ["Pipe"]}}"#,
+        ];
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry
+            .load_library_from_files(
+                "v1.0",
+                schemas
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| (format!("{}.json", i), s.to_string()))
+                    .collect(),
+            )
+            .unwrap();
+        let validator = SchemaValidator::new(registry);
+
+        // source: [Source, Compressor] -> pipe: [Pipe, Sink, Source, Pipe]
+        let branches = vec![
+            branch("source", &["pipe"], &["Source", "Compressor"]),
+            branch("pipe", &[], &["Pipe", "Sink", "Source", "Pipe"]),
+        ];
+        let edges = vec![Edge {
+            id: "source-pipe".to_string(),
+            source: "source".to_string(),
+            target: "pipe".to_string(),
+            data: EdgeData { weight: 1 },
+        }];
+        let network = Network {
+            id: "test".to_string(),
+            label: "Test".to_string(),
+            nodes: branches.into_iter().map(NodeData::Branch).collect(),
+            edges,
+        };
+
+        let codes = |id: &str, index: usize| -> Vec<&'static str> {
+            let Some(NodeData::Branch(branch)) = network.find_node(id) else {
+                panic!("Expected branch {}", id);
+            };
+            let ctx = BlockContext::in_network(&network, branch, index).unwrap();
+            validator
+                .validate_block_in_context(&ctx, "v1.0")
+                .issues
+                .iter()
+                .map(|i| i.code)
+                .filter(|c| *c != "unknown-property")
+                .collect()
+        };
+
+        assert!(codes("source", 0).is_empty());
+        // The first pipe follows the compressor at the end of the feeding branch
+        assert!(codes("pipe", 0).is_empty());
+        assert_eq!(codes("pipe", 1), vec!["not-last-in-branch"]);
+        assert_eq!(
+            codes("pipe", 2),
+            vec!["not-first-in-branch", "unexpected-incoming-edge"]
+        );
+        assert_eq!(codes("pipe", 3), vec!["invalid-predecessor"]);
+    }
+}
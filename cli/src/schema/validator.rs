@@ -1,43 +1,113 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dim::{validate_dimension, DimParser};
 use crate::parser::models::Block;
-use crate::schema::registry::SchemaRegistry;
+use crate::query::executor::toml_to_json;
+use crate::schema::registry::{Limit, PropertyMetadata, SchemaRegistry, ValueType};
+use crate::schema::topology::check_topology;
+use crate::scope::config::ScopeLevel;
+use crate::scope::resolver::{BlockContext, ScopeResolver};
+use regex::Regex;
+use serde::Serialize;
 use std::collections::HashSet;
+use toml::Value;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ValidationIssue {
+    /// Stable machine-readable identifier (e.g. "missing-required-property")
+    pub code: &'static str,
     pub severity: IssueSeverity,
     pub message: String,
     pub property: Option<String>,
+    /// The offending value, for constraint violations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    /// The constraint that was violated (e.g. "max 20 bar", "dimension pressure")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constraint: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum IssueSeverity {
     Error,
     Warning,
 }
 
+/// Where a required property's value came from
+#[derive(Debug, Clone, Serialize)]
+pub struct SatisfiedProperty {
+    pub property: String,
+    pub scope: ScopeLevel,
+    /// ID of the node holding the value (None for global and block-type defaults)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct ValidationResult {
     pub issues: Vec<ValidationIssue>,
+    /// Required properties that are present, and the scope supplying each
+    pub satisfied: Vec<SatisfiedProperty>,
 }
 
 impl ValidationResult {
     pub fn new() -> Self {
-        Self { issues: Vec::new() }
+        Self {
+            issues: Vec::new(),
+            satisfied: Vec::new(),
+        }
     }
 
-    pub fn add_error(&mut self, message: String, property: Option<String>) {
+    pub fn add_error(&mut self, code: &'static str, message: String, property: Option<String>) {
         self.issues.push(ValidationIssue {
+            code,
             severity: IssueSeverity::Error,
             message,
             property,
+            value: None,
+            constraint: None,
+        });
+    }
+
+    /// An error for a property value that violates a schema constraint
+    pub fn add_violation(
+        &mut self,
+        code: &'static str,
+        message: String,
+        property: &str,
+        value: &Value,
+        constraint: String,
+    ) {
+        self.issues.push(ValidationIssue {
+            code,
+            severity: IssueSeverity::Error,
+            message,
+            property: Some(property.to_string()),
+            value: Some(toml_to_json(value)),
+            constraint: Some(constraint),
         });
     }
 
-    pub fn add_warning(&mut self, message: String, property: Option<String>) {
+    /// An error for a block placed where its schema's topology constraints forbid
+    pub fn add_placement(&mut self, code: &'static str, message: String, constraint: String) {
         self.issues.push(ValidationIssue {
+            code,
+            severity: IssueSeverity::Error,
+            message,
+            property: None,
+            value: None,
+            constraint: Some(constraint),
+        });
+    }
+
+    pub fn add_warning(&mut self, code: &'static str, message: String, property: Option<String>) {
+        self.issues.push(ValidationIssue {
+            code,
             severity: IssueSeverity::Warning,
             message,
             property,
+            value: None,
+            constraint: None,
         });
     }
 
@@ -61,14 +131,46 @@ impl Default for ValidationResult {
 
 pub struct SchemaValidator {
     registry: SchemaRegistry,
+    resolver: Option<ScopeResolver>,
 }
 
 impl SchemaValidator {
     pub fn new(registry: SchemaRegistry) -> Self {
-        Self { registry }
+        Self {
+            registry,
+            resolver: None,
+        }
+    }
+
+    /// Let inherited values (branch, group, config.toml, ...) satisfy required
+    /// properties and be checked against the schema, in `validate_block_in_context`
+    pub fn with_scope_resolver(mut self, resolver: ScopeResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
     }
 
+    /// Validate only the properties set on the block itself
     pub fn validate_block(&self, block: &Block, schema_version: &str) -> ValidationResult {
+        self.validate(block, None, schema_version)
+    }
+
+    /// Validate a block in its network, resolving missing properties through the
+    /// scope chain when a resolver is set, and checking the schema's topology
+    /// constraints when the context includes the network
+    pub fn validate_block_in_context(
+        &self,
+        ctx: &BlockContext,
+        schema_version: &str,
+    ) -> ValidationResult {
+        self.validate(ctx.block, Some(ctx), schema_version)
+    }
+
+    fn validate(
+        &self,
+        block: &Block,
+        ctx: Option<&BlockContext>,
+        schema_version: &str,
+    ) -> ValidationResult {
         let mut result = ValidationResult::new();
 
         // Get schema for this block type
@@ -76,6 +178,7 @@ impl SchemaValidator {
             Some(s) => s,
             None => {
                 result.add_warning(
+                    "no-schema",
                     format!(
                         "No schema found for block type '{}' in version '{}'",
                         block.type_, schema_version
@@ -86,12 +189,67 @@ impl SchemaValidator {
             }
         };
 
+        if schema.is_abstract {
+            result.add_error(
+                "abstract-block-type",
+                format!(
+                    "Block type '{}' is an abstract base schema in version '{}' and can't be used directly",
+                    block.type_, schema_version
+                ),
+                None,
+            );
+        }
+
+        // Values inherited from the block's surroundings, for properties it doesn't set
+        let mut inherited: Vec<(&String, Value)> = Vec::new();
+        if let (Some(resolver), Some(ctx)) = (&self.resolver, ctx) {
+            let mut names: Vec<&String> = schema
+                .required_properties
+                .iter()
+                .chain(schema.properties.keys())
+                .filter(|name| !block.extra.contains_key(*name))
+                .collect();
+            names.sort();
+            names.dedup();
+
+            for name in names {
+                match resolver.explain(name, ctx) {
+                    Ok(trace) => {
+                        let Some(value) = trace.value else {
+                            continue;
+                        };
+                        if schema.required_properties.contains(name) {
+                            let step = trace.steps.iter().find(|s| s.selected);
+                            result.satisfied.push(SatisfiedProperty {
+                                property: name.clone(),
+                                scope: trace.scope.unwrap_or(ScopeLevel::Block),
+                                node: step.and_then(|s| s.node.clone()),
+                            });
+                        }
+                        inherited.push((name, value));
+                    }
+                    Err(e) => result.add_error(
+                        "resolve-failed",
+                        format!("Could not resolve '{}': {}", name, e),
+                        Some(name.clone()),
+                    ),
+                }
+            }
+        }
+
         // Check required properties
         let block_properties: HashSet<&String> = block.extra.keys().collect();
 
         for required_prop in &schema.required_properties {
-            if !block_properties.contains(required_prop) {
+            if block_properties.contains(required_prop) {
+                result.satisfied.push(SatisfiedProperty {
+                    property: required_prop.clone(),
+                    scope: ScopeLevel::Block,
+                    node: ctx.map(|ctx| ctx.branch.base.id.clone()),
+                });
+            } else if !inherited.iter().any(|(name, _)| *name == required_prop) {
                 result.add_error(
+                    "missing-required-property",
                     format!(
                         "Required property '{}' is missing for block type '{}'",
                         required_prop, block.type_
@@ -100,6 +258,22 @@ impl SchemaValidator {
                 );
             }
         }
+        result.satisfied.sort_by(|a, b| a.property.cmp(&b.property));
+
+        // Where the block sits: its position in the branch and the edges around it
+        if let Some(ctx) = ctx {
+            if let Some((network, index)) = ctx.network {
+                check_topology(
+                    &self.registry,
+                    schema_version,
+                    schema,
+                    network,
+                    ctx.branch,
+                    index,
+                    &mut result,
+                );
+            }
+        }
 
         // Warn about unknown properties (not in required or optional)
         let known_properties: HashSet<&String> = schema
@@ -111,6 +285,7 @@ impl SchemaValidator {
         for prop in &block_properties {
             if !known_properties.contains(prop) {
                 result.add_warning(
+                    "unknown-property",
                     format!(
                         "Unknown property '{}' for block type '{}' (not in schema version '{}')",
                         prop, block.type_, schema_version
@@ -120,6 +295,419 @@ impl SchemaValidator {
             }
         }
 
+        // Check values against each property's type, dimension and range
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut parser = DimParser::new().ok();
+        #[cfg(target_arch = "wasm32")]
+        let mut parser: Option<DimParser> = None;
+        let mut properties: Vec<_> = schema.properties.iter().collect();
+        properties.sort_by_key(|(name, _)| name.as_str());
+        for (name, meta) in properties {
+            if let Some(value) = block.extra.get(name) {
+                let original = block
+                    .extra
+                    .get(&format!("_{}_original", name))
+                    .and_then(|v| v.as_str());
+                check_value(name, value, original, meta, parser.as_mut(), &mut result);
+            } else if let Some((_, value)) = inherited.iter().find(|(n, _)| *n == name) {
+                check_value(name, value, None, meta, parser.as_mut(), &mut result);
+            }
+        }
+
         result
     }
 }
+
+/// Type, allowed-value, pattern, dimension and range checks for one property value
+/// Numbers are already normalized to base SI units; `original` is the unit string
+/// they were parsed from, if any. Array elements are checked against `items`
+fn check_value(
+    name: &str,
+    value: &Value,
+    original: Option<&str>,
+    meta: &PropertyMetadata,
+    mut parser: Option<&mut DimParser>,
+    result: &mut ValidationResult,
+) {
+    // Shown alongside the normalized value in messages
+    let shown = match original {
+        Some(original) => format!("{} ({})", value, original),
+        None => value.to_string(),
+    };
+
+    if let Some(expected) = expected_type(meta) {
+        if !has_type(value, expected) {
+            result.add_violation(
+                "type-mismatch",
+                format!(
+                    "Property '{}' must be of type {}, found {} {}",
+                    name,
+                    expected.as_str(),
+                    value.type_str(),
+                    shown
+                ),
+                name,
+                value,
+                format!("type {}", expected.as_str()),
+            );
+            return;
+        }
+    }
+
+    if let Some(allowed) = &meta.allowed_values {
+        if !allowed.iter().any(|a| same_value(a, value)) {
+            let choices = allowed
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            result.add_violation(
+                "not-allowed-value",
+                format!("Property '{}' = {} is not one of: {}", name, shown, choices),
+                name,
+                value,
+                format!("one of {}", choices),
+            );
+        }
+    }
+
+    if let (Some(pattern), Value::String(text)) = (&meta.pattern, value) {
+        match Regex::new(&format!("^(?:{})$", pattern)) {
+            Ok(regex) if !regex.is_match(text) => result.add_violation(
+                "pattern-mismatch",
+                format!(
+                    "Property '{}' = {} doesn't match the pattern {}",
+                    name, shown, pattern
+                ),
+                name,
+                value,
+                format!("pattern {}", pattern),
+            ),
+            Ok(_) => {}
+            Err(e) => result.add_warning(
+                "invalid-pattern",
+                format!("Schema pattern for '{}' is not a valid regex: {}", name, e),
+                Some(name.to_string()),
+            ),
+        }
+    }
+
+    if let (Some(items), Value::Array(elements)) = (&meta.items, value) {
+        for (index, element) in elements.iter().enumerate() {
+            let element_name = format!("{}[{}]", name, index);
+            check_value(
+                &element_name,
+                element,
+                None,
+                items,
+                parser.as_deref_mut(),
+                result,
+            );
+        }
+    }
+
+    let number = match value {
+        Value::Integer(i) => *i as f64,
+        Value::Float(f) => *f,
+        _ => return,
+    };
+
+    // Only a unit string can carry the wrong dimension; bare numbers are taken as SI
+    #[cfg(not(target_arch = "wasm32"))]
+    if let (Some(dimension), Some(original), Some(parser)) =
+        (&meta.dimension, original, parser.as_deref_mut())
+    {
+        if let Ok(parsed) = parser.parse_unit_string(original) {
+            if validate_dimension(parser, &parsed, dimension).is_err() {
+                result.add_violation(
+                    "dimension-mismatch",
+                    format!("Property '{}' = {} is not a {}", name, shown, dimension),
+                    name,
+                    value,
+                    format!("dimension {}", dimension),
+                );
+                return;
+            }
+        }
+    }
+
+    if let Some(min) = &meta.min {
+        if normalize_limit(min, parser.as_deref_mut()).is_some_and(|bound| number < bound) {
+            result.add_violation(
+                "below-minimum",
+                format!(
+                    "Property '{}' = {} is below the minimum {}",
+                    name, shown, min
+                ),
+                name,
+                value,
+                format!("min {}", min),
+            );
+        }
+    }
+    if let Some(max) = &meta.max {
+        if normalize_limit(max, parser).is_some_and(|bound| number > bound) {
+            result.add_violation(
+                "above-maximum",
+                format!(
+                    "Property '{}' = {} is above the maximum {}",
+                    name, shown, max
+                ),
+                name,
+                value,
+                format!("max {}", max),
+            );
+        }
+    }
+}
+
+/// The declared type, or the one implied by the other constraints: arrays have
+/// `items`, dimensioned or bounded properties are numeric
+fn expected_type(meta: &PropertyMetadata) -> Option<ValueType> {
+    meta.value_type.or_else(|| {
+        if meta.items.is_some() {
+            Some(ValueType::Array)
+        } else if meta.dimension.is_some() || meta.min.is_some() || meta.max.is_some() {
+            Some(ValueType::Number)
+        } else {
+            None
+        }
+    })
+}
+
+fn has_type(value: &Value, expected: ValueType) -> bool {
+    match (expected, value) {
+        (ValueType::Number, Value::Integer(_) | Value::Float(_)) => true,
+        (ValueType::Integer, Value::Integer(_)) => true,
+        (ValueType::Integer, Value::Float(f)) => f.fract() == 0.0,
+        (ValueType::String, Value::String(_)) => true,
+        (ValueType::Boolean, Value::Boolean(_)) => true,
+        (ValueType::Array, Value::Array(_)) => true,
+        _ => false,
+    }
+}
+
+/// Equality that treats 1 and 1.0 as the same value
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Integer(x), Value::Float(y)) | (Value::Float(y), Value::Integer(x)) => {
+            *x as f64 == *y
+        }
+        _ => a == b,
+    }
+}
+
+/// A limit in base SI units; quantities are skipped when they can't be parsed
+fn normalize_limit(limit: &Limit, parser: Option<&mut DimParser>) -> Option<f64> {
+    match limit {
+        Limit::Number(n) => Some(*n),
+        #[cfg(not(target_arch = "wasm32"))]
+        Limit::Quantity(quantity) => parser?
+            .parse_unit_string(quantity)
+            .ok()
+            .map(|parsed| parsed.value),
+        #[cfg(target_arch = "wasm32")]
+        Limit::Quantity(_) => parser.map(|parser| match *parser {}),
+    }
+}
+
+/// WASM builds have no unit parser, so dimensions and limits written with a
+/// unit aren't checked there
+#[cfg(target_arch = "wasm32")]
+enum DimParser {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn validator() -> SchemaValidator {
+        let schema = r#"{
+            "block_type": "Pipe",
+            "version": "v1.0",
+            "required": ["length"],
+            "optional": ["insulated", "material"],
+            "properties": {
+                "length": { "dimension": "length", "min": 0, "max": 100000 },
+                "insulated": { "type": "boolean" },
+                "material": { "type": "string" }
+            }
+        }"#;
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry
+            .load_library_from_files(
+                "v1.0",
+                HashMap::from([("pipe.json".to_string(), schema.to_string())]),
+            )
+            .unwrap();
+        SchemaValidator::new(registry)
+    }
+
+    fn pipe(properties: &[(&str, Value)]) -> Block {
+        Block {
+            quantity: Some(1),
+            type_: "Pipe".to_string(),
+            extra: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_range_and_type_violations_are_distinct_issues() {
+        let validator = validator();
+
+        let result = validator.validate_block(
+            &pipe(&[
+                ("length", Value::Float(250000.0)),
+                ("insulated", Value::String("yes".to_string())),
+                ("material", Value::String("steel".to_string())),
+            ]),
+            "v1.0",
+        );
+        let codes: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert_eq!(codes, vec!["type-mismatch", "above-maximum"]);
+
+        let above = &result.issues[1];
+        assert_eq!(above.property.as_deref(), Some("length"));
+        assert_eq!(above.value, Some(serde_json::json!(250000.0)));
+        assert_eq!(above.constraint.as_deref(), Some("max 100000"));
+
+        let result = validator.validate_block(
+            &pipe(&[("length", Value::String("far".to_string()))]),
+            "v1.0",
+        );
+        assert_eq!(result.issues[0].code, "type-mismatch");
+        assert_eq!(result.issues[0].constraint.as_deref(), Some("type number"));
+
+        let result = validator.validate_block(&pipe(&[("length", Value::Integer(-5))]), "v1.0");
+        assert_eq!(result.issues[0].code, "below-minimum");
+    }
+
+    #[test]
+    fn test_enum_pattern_integer_and_array_items() {
+        let schema = r#"{
+            "block_type": "CaptureUnit",
+            "version": "v1.0",
+            "description": "Post-combustion capture plant",
+            "required": [],
+            "optional": ["capture_technology", "tag", "trains", "stages"],
+            "properties": {
+                "capture_technology": { "enum": ["amine", "membrane"], "default": "amine" },
+                "tag": { "type": "string", "pattern": "CU-[0-9]+" },
+                "trains": { "type": "integer", "min": 1 },
+                "stages": { "items": { "type": "string", "enum": ["absorber", "stripper"] } }
+            }
+        }"#;
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry
+            .load_library_from_files(
+                "v1.0",
+                HashMap::from([("capture.json".to_string(), schema.to_string())]),
+            )
+            .unwrap();
+        let definition = registry.get_schema("v1.0", "CaptureUnit").unwrap();
+        assert_eq!(
+            definition.properties["capture_technology"].default,
+            Some(Value::String("amine".to_string()))
+        );
+        let validator = SchemaValidator::new(registry);
+
+        let block = |properties: &[(&str, Value)]| Block {
+            type_: "CaptureUnit".to_string(),
+            ..pipe(properties)
+        };
+        let stages: Value =
+            toml::from_str::<toml::Table>("s = [\"absorber\", \"reboiler\"]").unwrap()["s"].clone();
+        let result = validator.validate_block(
+            &block(&[
+                ("capture_technology", Value::String("cryogenic".to_string())),
+                ("tag", Value::String("CU-7a".to_string())),
+                ("trains", Value::Float(2.5)),
+                ("stages", stages),
+            ]),
+            "v1.0",
+        );
+        let issues: Vec<(&str, Option<&str>)> = result
+            .issues
+            .iter()
+            .map(|i| (i.code, i.property.as_deref()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                ("not-allowed-value", Some("capture_technology")),
+                ("not-allowed-value", Some("stages[1]")),
+                ("pattern-mismatch", Some("tag")),
+                ("type-mismatch", Some("trains")),
+            ]
+        );
+
+        let result = validator.validate_block(
+            &block(&[
+                ("capture_technology", Value::String("amine".to_string())),
+                ("tag", Value::String("CU-12".to_string())),
+                ("trains", Value::Integer(2)),
+            ]),
+            "v1.0",
+        );
+        assert!(!result.has_issues());
+    }
+
+    #[test]
+    fn test_required_property_satisfied_by_inheritance() {
+        use crate::parser::models::{BranchNode, NodeBase, Position};
+        use crate::scope::config::Config;
+
+        let branch = |length: Option<f64>, block: Block| BranchNode {
+            base: NodeBase {
+                id: "branch-1".to_string(),
+                type_: "branch".to_string(),
+                label: None,
+                position: Position { x: 0.0, y: 0.0 },
+                parent_id: None,
+                width: None,
+                height: None,
+                extra: length
+                    .map(|l| HashMap::from([("length".to_string(), Value::Float(l))]))
+                    .unwrap_or_default(),
+            },
+            outgoing: vec![],
+            blocks: vec![block],
+        };
+        let resolver = ScopeResolver::new(Config::load_from_str("").unwrap());
+        let validator = validator().with_scope_resolver(resolver);
+
+        // Without context the block alone is missing `length`
+        let inherited = branch(Some(50.0), pipe(&[]));
+        let ctx = BlockContext::detached(&inherited.blocks[0], &inherited);
+        assert_eq!(
+            validator.validate_block(ctx.block, "v1.0").issues[0].code,
+            "missing-required-property"
+        );
+
+        let result = validator.validate_block_in_context(&ctx, "v1.0");
+        assert!(!result.has_issues());
+        assert_eq!(result.satisfied.len(), 1);
+        assert_eq!(result.satisfied[0].scope, ScopeLevel::Branch);
+        assert_eq!(result.satisfied[0].node.as_deref(), Some("branch-1"));
+
+        // Inherited values are still checked against the schema
+        let too_long = branch(Some(250000.0), pipe(&[]));
+        let ctx = BlockContext::detached(&too_long.blocks[0], &too_long);
+        let result = validator.validate_block_in_context(&ctx, "v1.0");
+        assert_eq!(result.issues[0].code, "above-maximum");
+
+        let local = branch(None, pipe(&[("length", Value::Float(10.0))]));
+        let ctx = BlockContext::detached(&local.blocks[0], &local);
+        let result = validator.validate_block_in_context(&ctx, "v1.0");
+        assert_eq!(result.satisfied[0].scope, ScopeLevel::Block);
+
+        let missing = branch(None, pipe(&[]));
+        let ctx = BlockContext::detached(&missing.blocks[0], &missing);
+        let result = validator.validate_block_in_context(&ctx, "v1.0");
+        assert_eq!(result.issues[0].code, "missing-required-property");
+        assert!(result.satisfied.is_empty());
+    }
+}
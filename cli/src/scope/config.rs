@@ -12,9 +12,33 @@ pub struct Config {
     #[serde(default)]
     pub inheritance: InheritanceConfig,
 
+    // Dimensions of properties without schema metadata: "ambientTemperature" -> "temperature"
+    #[serde(default)]
+    pub dimensions: HashMap<String, String>,
+
+    // Per-block-type property defaults: "Pipe" -> { "roughness" -> 0.045 }
+    #[serde(default, rename = "blockDefaults")]
+    pub block_defaults: HashMap<String, HashMap<String, Value>>,
+
     // Unit preferences for display
     #[serde(default, rename = "unitPreferences")]
     pub unit_preferences: UnitPreferences,
+
+    // Schema version the network was authored against: "v1.0"
+    #[serde(
+        default,
+        rename = "schemaVersion",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub schema_version: Option<String>,
+
+    // Schema libraries directory, relative to the network directory
+    #[serde(
+        default,
+        rename = "schemasDir",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub schemas_dir: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -26,6 +50,10 @@ pub struct InheritanceConfig {
     // Per-property inheritance rules
     #[serde(default)]
     pub rules: HashMap<String, PropertyInheritanceRule>,
+
+    // How the upstream scope combines values from several incoming branches
+    #[serde(default, rename = "upstreamMerge")]
+    pub upstream_merge: UpstreamMerge,
 }
 
 impl Default for InheritanceConfig {
@@ -33,6 +61,7 @@ impl Default for InheritanceConfig {
         Self {
             general: default_general_inheritance(),
             rules: HashMap::new(),
+            upstream_merge: UpstreamMerge::default(),
         }
     }
 }
@@ -47,9 +76,39 @@ pub enum PropertyInheritanceRule {
         inheritance: Vec<ScopeLevel>,
         #[serde(default)]
         overrides: HashMap<String, Vec<ScopeLevel>>,
+        // Overrides [inheritance] upstreamMerge for this property
+        #[serde(default, rename = "upstreamMerge")]
+        upstream_merge: Option<UpstreamMerge>,
+        // How table values found at several scopes combine
+        #[serde(default)]
+        merge: MergeMode,
     },
 }
 
+/// How a property's values across the scope chain combine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeMode {
+    /// The nearest scope's value wins outright
+    #[default]
+    Replace,
+    /// Tables are merged field by field, nearer scopes winning per leaf
+    Deep,
+}
+
+/// How the upstream scope combines values arriving from several incoming branches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpstreamMerge {
+    /// Take the value from the first incoming edge
+    #[default]
+    FirstWins,
+    /// Fail when incoming branches disagree
+    Error,
+    /// Average weighted by edge weight (numbers, or tables of numbers key by key)
+    Blend,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ScopeLevel {
@@ -57,6 +116,50 @@ pub enum ScopeLevel {
     Group,
     Branch,
     Block,
+    // Defaults for the block's type from [blockDefaults.<Type>]
+    #[serde(rename = "blockType")]
+    BlockType,
+    // Nearest value set on an earlier block or an upstream branch
+    Upstream,
+}
+
+impl ScopeLevel {
+    pub const ALL: [ScopeLevel; 6] = [
+        ScopeLevel::Global,
+        ScopeLevel::Group,
+        ScopeLevel::Branch,
+        ScopeLevel::Block,
+        ScopeLevel::BlockType,
+        ScopeLevel::Upstream,
+    ];
+
+    /// Name as written in config.toml and query strings
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScopeLevel::Global => "global",
+            ScopeLevel::Group => "group",
+            ScopeLevel::Branch => "branch",
+            ScopeLevel::Block => "block",
+            ScopeLevel::BlockType => "blockType",
+            ScopeLevel::Upstream => "upstream",
+        }
+    }
+}
+
+impl std::str::FromStr for ScopeLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "global" => Ok(ScopeLevel::Global),
+            "group" => Ok(ScopeLevel::Group),
+            "branch" => Ok(ScopeLevel::Branch),
+            "block" => Ok(ScopeLevel::Block),
+            "blocktype" | "block_type" => Ok(ScopeLevel::BlockType),
+            "upstream" => Ok(ScopeLevel::Upstream),
+            other => Err(format!("Unknown scope level: {}", other)),
+        }
+    }
 }
 
 fn default_general_inheritance() -> Vec<ScopeLevel> {
@@ -88,8 +191,13 @@ impl Config {
             inheritance: InheritanceConfig {
                 general: default_general_inheritance(),
                 rules: HashMap::new(),
+                upstream_merge: UpstreamMerge::default(),
             },
+            dimensions: HashMap::new(),
+            block_defaults: HashMap::new(),
             unit_preferences: UnitPreferences::default(),
+            schema_version: None,
+            schemas_dir: None,
         }
     }
 }
@@ -0,0 +1,399 @@
+// Layered configuration: user preferences, `extends` chains and the network's config.toml
+
+use crate::scope::config::Config;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Environment variable naming the user-level config file
+pub const USER_CONFIG_ENV: &str = "DAGGER_CONFIG";
+
+/// Where a layer sits in the precedence order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerKind {
+    /// The user-level preferences file (lowest precedence); only its
+    /// `[unitPreferences]` apply
+    User,
+    /// A file pulled in through `extends`
+    Extends,
+    /// The network directory's own config.toml (highest precedence)
+    Network,
+}
+
+/// One file contributing to the effective configuration
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigLayer {
+    pub path: PathBuf,
+    pub kind: LayerKind,
+}
+
+/// All config files that apply to a network, merged lowest precedence first:
+/// the user-level file (display preferences only), then the `extends` chain
+/// (furthest base first), then the network's config.toml. Tables merge key by
+/// key; any other value (including inheritance chains) is replaced whole by a
+/// higher layer
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    /// Layers in the order they were applied
+    pub layers: Vec<ConfigLayer>,
+    /// The merged document, without `extends` keys
+    pub merged: Table,
+    /// Key path of every merged leaf -> index into `layers`
+    sources: BTreeMap<Vec<String>, usize>,
+}
+
+impl LayeredConfig {
+    /// Load a network's config.toml and the files it extends. These are what
+    /// loading, resolution and validation use. A missing config.toml contributes nothing
+    pub fn load(config_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load_with_user_file(config_path, None)
+    }
+
+    /// Like `load`, with the `[unitPreferences]` of the user-level file from
+    /// [`user_config_path`] underneath, for display
+    pub fn load_with_user_preferences(
+        config_path: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load_with_user_file(config_path, user_config_path().as_deref())
+    }
+
+    /// Like `load`, with the `[unitPreferences]` of `user_file` underneath; the
+    /// rest of the user file is ignored
+    pub fn load_with_user_file(
+        config_path: &Path,
+        user_file: Option<&Path>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut files: Vec<(ConfigLayer, Table)> = Vec::new();
+
+        if let Some(user_file) = user_file {
+            let mut table = read_table(user_file)?;
+            table.retain(|key, _| key == "unitPreferences");
+            files.push((
+                ConfigLayer {
+                    path: user_file.to_path_buf(),
+                    kind: LayerKind::User,
+                },
+                table,
+            ));
+        }
+
+        if config_path.exists() {
+            // Walk the extends chain from the network file outwards, then apply
+            // the bases before the files that extend them
+            let mut chain = vec![(
+                ConfigLayer {
+                    path: config_path.to_path_buf(),
+                    kind: LayerKind::Network,
+                },
+                read_table(config_path)?,
+            )];
+            let mut seen = vec![canonical(config_path)];
+
+            while let Some(extends) = chain.last().and_then(|(_, table)| extends_of(table)) {
+                let (layer, _) = chain.last().expect("chain is never empty");
+                let base = resolve_extends(&layer.path, &extends?)?;
+
+                let canonical_base = canonical(&base);
+                if let Some(start) = seen.iter().position(|p| *p == canonical_base) {
+                    let mut cycle: Vec<String> = chain[start..]
+                        .iter()
+                        .map(|(l, _)| l.path.display().to_string())
+                        .collect();
+                    cycle.push(base.display().to_string());
+                    return Err(format!("Config extends cycle: {}", cycle.join(" -> ")).into());
+                }
+                seen.push(canonical_base);
+
+                let table = read_table(&base)?;
+                chain.push((
+                    ConfigLayer {
+                        path: base,
+                        kind: LayerKind::Extends,
+                    },
+                    table,
+                ));
+            }
+
+            files.extend(chain.into_iter().rev());
+        }
+
+        let mut layered = Self {
+            layers: Vec::new(),
+            merged: Table::new(),
+            sources: BTreeMap::new(),
+        };
+        for (index, (layer, mut table)) in files.into_iter().enumerate() {
+            table.remove("extends");
+            merge_table(&mut layered.merged, table, &[], index, &mut layered.sources);
+            layered.layers.push(layer);
+        }
+
+        Ok(layered)
+    }
+
+    /// The effective configuration
+    pub fn config(&self) -> Result<Config, Box<dyn std::error::Error>> {
+        Ok(Value::Table(self.merged.clone()).try_into()?)
+    }
+
+    /// Layer that supplied a dotted key ("inheritance.rules.pressure"), or the
+    /// layer of its first leaf when the key names a table
+    pub fn source_of(&self, key: &str) -> Option<&ConfigLayer> {
+        let parts: Vec<String> = key.split('.').map(|s| s.to_string()).collect();
+        let index = self.sources.get(&parts).or_else(|| {
+            self.sources
+                .range(parts.clone()..)
+                .next()
+                .filter(|(path, _)| path.starts_with(&parts))
+                .map(|(_, index)| index)
+        })?;
+        self.layers.get(*index)
+    }
+
+    /// Every merged leaf as (dotted key, value, layer), sorted by key
+    pub fn entries(&self) -> Vec<(String, &Value, &ConfigLayer)> {
+        self.sources
+            .iter()
+            .filter_map(|(path, index)| {
+                let value = lookup(&self.merged, path)?;
+                Some((dotted(path), value, &self.layers[*index]))
+            })
+            .collect()
+    }
+}
+
+/// The user-level config file: `$DAGGER_CONFIG`, else
+/// `$XDG_CONFIG_HOME/dagger/config.toml`, else `~/.config/dagger/config.toml`,
+/// if it exists
+pub fn user_config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(USER_CONFIG_ENV) {
+        return Some(PathBuf::from(path)).filter(|p| p.exists());
+    }
+
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("dagger").join("config.toml")).filter(|p| p.exists())
+}
+
+fn read_table(path: &Path) -> Result<Table, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read config {}: {}", path.display(), e))?;
+    content
+        .parse::<Table>()
+        .map_err(|e| format!("Invalid config {}: {}", path.display(), e).into())
+}
+
+fn extends_of(table: &Table) -> Option<Result<String, String>> {
+    table.get("extends").map(|value| {
+        value
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| "extends must be a path string".to_string())
+    })
+}
+
+/// Resolve an `extends` path relative to the file that declares it
+fn resolve_extends(from: &Path, extends: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let base = from.parent().unwrap_or(Path::new(".")).join(extends);
+    if !base.exists() {
+        return Err(format!(
+            "{} extends '{}', which was not found at {}",
+            from.display(),
+            extends,
+            base.display()
+        )
+        .into());
+    }
+    Ok(base)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Key path as written in TOML, quoting segments that aren't bare keys
+fn dotted(path: &[String]) -> String {
+    path.iter()
+        .map(|key| {
+            let bare = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if bare {
+                key.clone()
+            } else {
+                format!("{:?}", key)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn merge_table(
+    base: &mut Table,
+    overlay: Table,
+    prefix: &[String],
+    layer: usize,
+    sources: &mut BTreeMap<Vec<String>, usize>,
+) {
+    for (key, value) in overlay {
+        let path = [prefix, std::slice::from_ref(&key)].concat();
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(incoming)) => {
+                merge_table(existing, incoming, &path, layer, sources);
+            }
+            (_, value) => {
+                // The whole subtree now comes from this layer
+                sources.retain(|k, _| !k.starts_with(&path));
+                record_leaves(&value, path, layer, sources);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record_leaves(
+    value: &Value,
+    path: Vec<String>,
+    layer: usize,
+    sources: &mut BTreeMap<Vec<String>, usize>,
+) {
+    match value {
+        Value::Table(table) if !table.is_empty() => {
+            for (key, value) in table {
+                record_leaves(
+                    value,
+                    [path.as_slice(), std::slice::from_ref(key)].concat(),
+                    layer,
+                    sources,
+                );
+            }
+        }
+        _ => {
+            sources.insert(path, layer);
+        }
+    }
+}
+
+fn lookup<'t>(table: &'t Table, path: &[String]) -> Option<&'t Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+    for key in parents {
+        table = table.get(key)?.as_table()?;
+    }
+    table.get(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scope::config::ScopeLevel;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("dagger-layers-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_layers_merge_with_precedence_and_provenance() {
+        let dir = temp_dir("merge");
+        let user = write(
+            &dir,
+            "user.toml",
+            "roughness = 1.0\n[properties]\nroughness = 0.1\n\n[unitPreferences.dimensions]\nlength = \"ft\"\npressure = \"psi\"\n",
+        );
+        write(
+            &dir,
+            "shared/config.toml",
+            r#"
+[properties]
+ambientTemperature = 15.0
+pressure = 10.0
+
+[inheritance.rules]
+ambientTemperature = ["group", "global"]
+
+[unitPreferences.dimensions]
+length = "km"
+"#,
+        );
+        let network = write(
+            &dir,
+            "net/config.toml",
+            r#"
+extends = "../shared/config.toml"
+
+[properties]
+pressure = 14.7
+
+[inheritance.rules]
+ambientTemperature = ["block", "global"]
+"#,
+        );
+
+        let layered = LayeredConfig::load_with_user_file(&network, Some(&user)).unwrap();
+        let kinds: Vec<LayerKind> = layered.layers.iter().map(|l| l.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![LayerKind::User, LayerKind::Extends, LayerKind::Network]
+        );
+
+        let config = layered.config().unwrap();
+        assert_eq!(config.properties["pressure"].as_float(), Some(14.7));
+        assert_eq!(
+            config.properties["ambientTemperature"].as_float(),
+            Some(15.0)
+        );
+        // Only display preferences come from the user file
+        assert!(!config.properties.contains_key("roughness"));
+        assert!(!layered.merged.contains_key("roughness"));
+        assert_eq!(config.unit_preferences.dimensions["length"], "km");
+        assert_eq!(config.unit_preferences.dimensions["pressure"], "psi");
+        match &config.inheritance.rules["ambientTemperature"] {
+            crate::scope::config::PropertyInheritanceRule::Simple(chain) => {
+                assert_eq!(chain, &vec![ScopeLevel::Block, ScopeLevel::Global])
+            }
+            _ => panic!("Expected simple rule"),
+        }
+
+        let kind_of = |key: &str| layered.source_of(key).map(|l| l.kind);
+        assert_eq!(kind_of("properties.pressure"), Some(LayerKind::Network));
+        assert_eq!(
+            kind_of("properties.ambientTemperature"),
+            Some(LayerKind::Extends)
+        );
+        assert_eq!(
+            kind_of("unitPreferences.dimensions.pressure"),
+            Some(LayerKind::User)
+        );
+        assert!(layered
+            .entries()
+            .iter()
+            .all(|(key, _, _)| *key != "extends"));
+    }
+
+    #[test]
+    fn test_extends_cycle_is_an_error() {
+        let dir = temp_dir("cycle");
+        write(&dir, "a/config.toml", "extends = \"../b/config.toml\"\n");
+        write(&dir, "b/config.toml", "extends = \"../a/config.toml\"\n");
+
+        let err = LayeredConfig::load_with_user_file(&dir.join("a/config.toml"), None)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("extends cycle"), "{}", err);
+    }
+}
@@ -0,0 +1,394 @@
+// Materialization of inherited properties into an explicit ("effective") network
+
+use crate::parser::models::*;
+use crate::schema::registry::SchemaRegistry;
+use crate::scope::config::ScopeLevel;
+use crate::scope::resolver::{BlockContext, ResolveError, ScopeResolver};
+use std::collections::BTreeSet;
+use toml::Value;
+
+/// Key under which the scope a materialized property came from is recorded
+/// (e.g. "ambientTemperature" -> "_ambientTemperature_scope")
+pub fn scope_key(property: &str) -> String {
+    format!("_{}_scope", property)
+}
+
+/// Whether a key holds metadata about another property rather than a property itself
+pub fn is_metadata_key(key: &str) -> bool {
+    key.starts_with('_') && (key.ends_with("_original") || key.ends_with("_scope"))
+}
+
+impl Network {
+    /// Produce a network where every block carries every property it would resolve to
+    /// Candidates are the block's own properties, config.toml [properties], the
+    /// block type's [blockDefaults.<Type>] and, when the upstream scope is used,
+    /// every property set on any block
+    pub fn materialize(&self, resolver: &ScopeResolver) -> Result<Network, ResolveError> {
+        self.materialize_blocks(resolver, |_| Vec::new())
+    }
+
+    /// Like `materialize`, additionally resolving every property the block type's
+    /// schema declares
+    pub fn materialize_with_schema(
+        &self,
+        resolver: &ScopeResolver,
+        registry: &SchemaRegistry,
+        version: &str,
+    ) -> Result<Network, ResolveError> {
+        self.materialize_blocks(resolver, |block_type| {
+            registry
+                .get_schema(version, block_type)
+                .map(|schema| {
+                    schema
+                        .required_properties
+                        .iter()
+                        .chain(schema.optional_properties.iter())
+                        .chain(schema.properties.keys())
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    fn materialize_blocks<F>(
+        &self,
+        resolver: &ScopeResolver,
+        schema_properties: F,
+    ) -> Result<Network, ResolveError>
+    where
+        F: Fn(&str) -> Vec<String>,
+    {
+        let global_properties: Vec<String> = resolver
+            .global_property_names()
+            .into_iter()
+            .cloned()
+            .collect();
+
+        // Anything set on a block can flow downstream when the upstream scope is in use
+        let upstream_properties: BTreeSet<String> = if resolver.uses_scope(ScopeLevel::Upstream) {
+            self.nodes
+                .iter()
+                .filter_map(|n| match n {
+                    NodeData::Branch(b) => Some(b),
+                    _ => None,
+                })
+                .flat_map(|b| b.blocks.iter())
+                .flat_map(|block| block.extra.keys())
+                .filter(|k| !is_metadata_key(k))
+                .cloned()
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
+
+        let mut network = self.clone();
+
+        for (node, target) in self.nodes.iter().zip(network.nodes.iter_mut()) {
+            let (NodeData::Branch(branch), NodeData::Branch(target)) = (node, target) else {
+                continue;
+            };
+
+            let mut materialized_blocks = Vec::with_capacity(branch.blocks.len());
+            for (block_index, block) in branch.blocks.iter().enumerate() {
+                let ctx = BlockContext::in_network(self, branch, block_index)?;
+                let candidates: BTreeSet<String> = block
+                    .extra
+                    .keys()
+                    .cloned()
+                    .chain(global_properties.iter().cloned())
+                    .chain(
+                        resolver
+                            .block_default_names(&block.type_)
+                            .into_iter()
+                            .cloned(),
+                    )
+                    .chain(upstream_properties.iter().cloned())
+                    .chain(schema_properties(&block.type_))
+                    .filter(|k| !is_metadata_key(k))
+                    .collect();
+
+                let mut materialized = block.clone();
+                for property in candidates {
+                    let trace = resolver.explain(&property, &ctx)?;
+                    let (Some(value), Some(scope)) = (trace.value, trace.scope) else {
+                        continue;
+                    };
+
+                    // Carry the original unit string from the node that supplied the value
+                    let selected = trace.steps.iter().find(|step| step.selected);
+                    let source = selected.and_then(|step| step.node.as_deref());
+                    let original_key = format!("_{}_original", property);
+                    let original = match scope {
+                        ScopeLevel::Block => block.extra.get(&original_key),
+                        ScopeLevel::Branch => branch.base.extra.get(&original_key),
+                        ScopeLevel::Group => ctx
+                            .groups
+                            .iter()
+                            .find(|g| Some(g.base.id.as_str()) == source)
+                            .and_then(|g| g.base.extra.get(&original_key)),
+                        // Blended values have no single original
+                        ScopeLevel::Upstream => {
+                            match selected.map(|step| step.sources.as_slice()) {
+                                Some([only]) => match self.find_node(&only.node) {
+                                    Some(NodeData::Branch(b)) => b
+                                        .blocks
+                                        .get(only.block_index)
+                                        .and_then(|b| b.extra.get(&original_key)),
+                                    _ => None,
+                                },
+                                _ => None,
+                            }
+                        }
+                        ScopeLevel::Global => resolver.global_property(&original_key),
+                        ScopeLevel::BlockType => None,
+                    };
+                    match original {
+                        Some(original) => {
+                            materialized.extra.insert(original_key, original.clone());
+                        }
+                        None => {
+                            materialized.extra.remove(&original_key);
+                        }
+                    }
+
+                    materialized.extra.insert(property.clone(), value);
+                    materialized
+                        .extra
+                        .insert(scope_key(&property), scope_value(scope));
+                }
+                materialized_blocks.push(materialized);
+            }
+            target.blocks = materialized_blocks;
+        }
+
+        Ok(network)
+    }
+}
+
+fn scope_value(scope: ScopeLevel) -> Value {
+    Value::String(scope.as_str().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scope::config::Config;
+    use std::collections::HashMap;
+
+    fn node_base(id: &str, type_: &str, parent_id: Option<&str>) -> NodeBase {
+        NodeBase {
+            id: id.to_string(),
+            type_: type_.to_string(),
+            label: None,
+            position: Position { x: 0.0, y: 0.0 },
+            parent_id: parent_id.map(|p| p.to_string()),
+            width: None,
+            height: None,
+            extra: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_materialize_tags_scope_of_each_property() {
+        let mut group = GroupNode {
+            base: node_base("group-1", "labeledGroup", None),
+        };
+        group
+            .base
+            .extra
+            .insert("ambientTemperature".to_string(), Value::Float(288.15));
+
+        let mut block_extra = HashMap::new();
+        block_extra.insert("pressure".to_string(), Value::Float(15.5));
+        let branch = BranchNode {
+            base: node_base("branch-1", "branch", Some("group-1")),
+            outgoing: vec![],
+            blocks: vec![Block {
+                quantity: Some(1),
+                type_: "Source".to_string(),
+                extra: block_extra,
+            }],
+        };
+
+        let network = Network {
+            id: "test".to_string(),
+            label: "Test".to_string(),
+            nodes: vec![NodeData::Group(group), NodeData::Branch(branch)],
+            edges: vec![],
+        };
+
+        let config = Config::load_from_str(
+            r#"
+[properties]
+ambientTemperature = 293.15
+discountRate = 0.1
+"#,
+        )
+        .unwrap();
+        let resolver = ScopeResolver::new(config);
+
+        let materialized = network.materialize(&resolver).unwrap();
+        let NodeData::Branch(branch) = &materialized.nodes[1] else {
+            panic!("Expected Branch node");
+        };
+        let extra = &branch.blocks[0].extra;
+
+        assert_eq!(extra.get("ambientTemperature"), Some(&Value::Float(288.15)));
+        assert_eq!(
+            extra
+                .get("_ambientTemperature_scope")
+                .and_then(|v| v.as_str()),
+            Some("group")
+        );
+        assert_eq!(extra.get("discountRate"), Some(&Value::Float(0.1)));
+        assert_eq!(
+            extra.get("_discountRate_scope").and_then(|v| v.as_str()),
+            Some("global")
+        );
+        assert_eq!(
+            extra.get("_pressure_scope").and_then(|v| v.as_str()),
+            Some("block")
+        );
+    }
+
+    #[test]
+    fn test_materialize_walks_nested_groups_nearest_first() {
+        let mut outer = GroupNode {
+            base: node_base("outer", "labeledGroup", None),
+        };
+        outer
+            .base
+            .extra
+            .insert("ambientTemperature".to_string(), Value::Float(280.0));
+        outer
+            .base
+            .extra
+            .insert("roughness".to_string(), Value::Float(0.05));
+        let mut inner = GroupNode {
+            base: node_base("inner", "labeledGroup", Some("outer")),
+        };
+        inner
+            .base
+            .extra
+            .insert("ambientTemperature".to_string(), Value::Float(290.0));
+        let branch = BranchNode {
+            base: node_base("branch-1", "branch", Some("inner")),
+            outgoing: vec![],
+            blocks: vec![Block {
+                quantity: Some(1),
+                type_: "Pipe".to_string(),
+                extra: HashMap::new(),
+            }],
+        };
+
+        let mut network = Network {
+            id: "test".to_string(),
+            label: "Test".to_string(),
+            nodes: vec![
+                NodeData::Group(outer),
+                NodeData::Group(inner),
+                NodeData::Branch(branch),
+            ],
+            edges: vec![],
+        };
+
+        let config = Config::load_from_str(
+            r#"
+[properties]
+ambientTemperature = 293.15
+roughness = 0.01
+"#,
+        )
+        .unwrap();
+        let resolver = ScopeResolver::new(config);
+
+        let materialized = network.materialize(&resolver).unwrap();
+        let NodeData::Branch(branch) = &materialized.nodes[2] else {
+            panic!("Expected Branch node");
+        };
+        let extra = &branch.blocks[0].extra;
+        assert_eq!(extra.get("ambientTemperature"), Some(&Value::Float(290.0)));
+        assert_eq!(extra.get("roughness"), Some(&Value::Float(0.05)));
+
+        // Make the outer group point back at the inner one
+        if let NodeData::Group(outer) = &mut network.nodes[0] {
+            outer.base.parent_id = Some("inner".to_string());
+        }
+        let err = network.materialize(&resolver).unwrap_err();
+        assert_eq!(
+            err,
+            ResolveError::ParentCycle(ParentCycleError {
+                cycle: vec![
+                    "inner".to_string(),
+                    "outer".to_string(),
+                    "inner".to_string()
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_materialize_with_schema_resolves_schema_only_properties() {
+        let mut group = GroupNode {
+            base: node_base("group-1", "labeledGroup", None),
+        };
+        group
+            .base
+            .extra
+            .insert("insulation".to_string(), Value::String("foam".to_string()));
+        let branch = BranchNode {
+            base: node_base("branch-1", "branch", Some("group-1")),
+            outgoing: vec![],
+            blocks: vec![Block {
+                quantity: Some(1),
+                type_: "Pipe".to_string(),
+                extra: HashMap::new(),
+            }],
+        };
+        let network = Network {
+            id: "test".to_string(),
+            label: "Test".to_string(),
+            nodes: vec![NodeData::Group(group), NodeData::Branch(branch)],
+            edges: vec![],
+        };
+
+        let mut registry = SchemaRegistry::new("schemas".into());
+        registry
+            .load_library_from_files(
+                "v1.0",
+                HashMap::from([(
+                    "pipe.json".to_string(),
+                    r#"{"block_type": "Pipe", "version": "v1.0",
+                        "optional": ["insulation"],
+                        "properties": {"insulation": {"type": "string"}}}"#
+                        .to_string(),
+                )]),
+            )
+            .unwrap();
+        let resolver = ScopeResolver::new(Config::empty());
+
+        // Nothing names the group's property, so only the schema brings it in
+        let plain = network.materialize(&resolver).unwrap();
+        let NodeData::Branch(branch) = &plain.nodes[1] else {
+            panic!("Expected Branch node");
+        };
+        assert!(!branch.blocks[0].extra.contains_key("insulation"));
+
+        let materialized = network
+            .materialize_with_schema(&resolver, &registry, "v1.0")
+            .unwrap();
+        let NodeData::Branch(branch) = &materialized.nodes[1] else {
+            panic!("Expected Branch node");
+        };
+        let extra = &branch.blocks[0].extra;
+        assert_eq!(
+            extra.get("insulation"),
+            Some(&Value::String("foam".to_string()))
+        );
+        assert_eq!(
+            extra.get("_insulation_scope").and_then(|v| v.as_str()),
+            Some("group")
+        );
+    }
+}
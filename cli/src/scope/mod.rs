@@ -1,7 +1,15 @@
 pub mod config;
+pub mod layers;
+pub mod materialize;
 pub mod registry;
 pub mod resolver;
+pub mod upstream;
+pub mod validator;
 
 pub use config::*;
+pub use layers::*;
+pub use materialize::*;
 pub use registry::*;
 pub use resolver::*;
+pub use upstream::*;
+pub use validator::*;
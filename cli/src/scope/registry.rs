@@ -1,16 +1,151 @@
-// Registry for managing property configurations
-// This can be extended later for property metadata, validation, etc.
+// Catalog of known properties, merged from config.toml, schemas and the network
 
+use crate::parser::models::*;
+use crate::schema::registry::SchemaRegistry;
 use crate::scope::config::*;
+use crate::scope::materialize::is_metadata_key;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
 use toml::Value;
 
+/// Where a property's inheritance chain comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    /// `[inheritance] general`
+    General,
+    /// `[inheritance.rules.<property>]`
+    Property,
+}
+
+/// Everything known about one property
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertyInfo {
+    pub name: String,
+    /// Schema description, else its title
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// From the schema, else config.toml [dimensions]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_unit: Option<String>,
+    /// Global default from config.toml [properties]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    /// Scopes the property resolves through, nearest first
+    pub scopes: Vec<ScopeLevel>,
+    pub rule: RuleKind,
+    /// Per-block-type chains replacing `scopes`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, Vec<ScopeLevel>>,
+    /// Block types whose schema declares it, that set it in the network, or
+    /// that default it in [blockDefaults]
+    pub block_types: BTreeSet<String>,
+    /// Block types whose schema requires it
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub required_by: BTreeSet<String>,
+}
+
 pub struct PropertyRegistry {
     config: Config,
+    properties: BTreeMap<String, PropertyInfo>,
 }
 
 impl PropertyRegistry {
+    /// Catalog of the properties config.toml mentions; add schemas and a network
+    /// with `with_schemas` / `with_network`
     pub fn new(config: Config) -> Self {
-        Self { config }
+        let mut registry = Self {
+            config,
+            properties: BTreeMap::new(),
+        };
+
+        let config = registry.config.clone();
+        for name in config.properties.keys().filter(|k| !is_metadata_key(k)) {
+            registry.entry(name);
+        }
+        for name in config.inheritance.rules.keys() {
+            registry.entry(name);
+        }
+        for (name, dimension) in &config.dimensions {
+            registry.entry(name).dimension = Some(dimension.clone());
+        }
+        for (block_type, defaults) in &config.block_defaults {
+            for name in defaults.keys() {
+                registry.entry(name).block_types.insert(block_type.clone());
+            }
+        }
+
+        registry
+    }
+
+    /// Add every property declared by a schema library; schema metadata takes
+    /// precedence over config.toml [dimensions]
+    pub fn with_schemas(mut self, schemas: &SchemaRegistry, version: &str) -> Self {
+        for block_type in schemas.list_block_types(version) {
+            let Some(schema) = schemas.get_schema(version, block_type) else {
+                continue;
+            };
+
+            for name in &schema.required_properties {
+                let info = self.entry(name);
+                info.block_types.insert(block_type.clone());
+                info.required_by.insert(block_type.clone());
+            }
+            for name in &schema.optional_properties {
+                self.entry(name).block_types.insert(block_type.clone());
+            }
+            for (name, meta) in &schema.properties {
+                let info = self.entry(name);
+                info.block_types.insert(block_type.clone());
+                if let Some(description) = meta.description.as_ref().or(meta.title.as_ref()) {
+                    info.description = Some(description.clone());
+                }
+                if meta.dimension.is_some() {
+                    info.dimension = meta.dimension.clone();
+                }
+                if meta.default_unit.is_some() {
+                    info.default_unit = meta.default_unit.clone();
+                }
+            }
+        }
+        self
+    }
+
+    /// Add the properties set on blocks, branches and groups of a network
+    pub fn with_network(mut self, network: &Network) -> Self {
+        for node in &network.nodes {
+            for name in node.base().extra.keys().filter(|k| !is_metadata_key(k)) {
+                self.entry(name);
+            }
+            if let NodeData::Branch(branch) = node {
+                for block in &branch.blocks {
+                    for name in block.extra.keys().filter(|k| !is_metadata_key(k)) {
+                        self.entry(name).block_types.insert(block.type_.clone());
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PropertyInfo> {
+        self.properties.get(name)
+    }
+
+    /// All known properties, sorted by name
+    pub fn list(&self) -> Vec<&PropertyInfo> {
+        self.properties.values().collect()
+    }
+
+    /// Properties a block of this type can carry
+    pub fn for_block_type(&self, block_type: &str) -> Vec<&PropertyInfo> {
+        self.properties
+            .values()
+            .filter(|p| p.block_types.contains(block_type))
+            .collect()
     }
 
     pub fn get_global_property(&self, name: &str) -> Option<&Value> {
@@ -24,4 +159,125 @@ impl PropertyRegistry {
     pub fn has_inheritance_rule(&self, property: &str) -> bool {
         self.config.inheritance.rules.contains_key(property)
     }
+
+    /// Catalog entry for a property, created with its config-derived fields
+    fn entry(&mut self, name: &str) -> &mut PropertyInfo {
+        let config = &self.config;
+        self.properties.entry(name.to_string()).or_insert_with(|| {
+            let (scopes, rule, overrides) = match config.inheritance.rules.get(name) {
+                Some(PropertyInheritanceRule::Simple(chain)) => {
+                    (chain.clone(), RuleKind::Property, BTreeMap::new())
+                }
+                Some(PropertyInheritanceRule::Complex {
+                    inheritance,
+                    overrides,
+                    ..
+                }) => (
+                    inheritance.clone(),
+                    RuleKind::Property,
+                    overrides
+                        .iter()
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect(),
+                ),
+                None => (
+                    config.inheritance.general.clone(),
+                    RuleKind::General,
+                    BTreeMap::new(),
+                ),
+            };
+
+            PropertyInfo {
+                name: name.to_string(),
+                description: None,
+                dimension: config.dimensions.get(name).cloned(),
+                default_unit: None,
+                default: config.properties.get(name).cloned(),
+                scopes,
+                rule,
+                overrides,
+                block_types: BTreeSet::new(),
+                required_by: BTreeSet::new(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_catalog_merges_config_and_network() {
+        let config = Config::load_from_str(
+            r#"
+[properties]
+ambientTemperature = 20.0
+
+[dimensions]
+ambientTemperature = "temperature"
+
+[inheritance.rules]
+ambientTemperature = ["group", "global"]
+roughness = { inheritance = ["block", "blockType"], overrides = { Pipe = ["block"] } }
+
+[blockDefaults.Pipe]
+roughness = 0.045
+"#,
+        )
+        .unwrap();
+
+        let mut extra = HashMap::new();
+        extra.insert("length".to_string(), Value::Float(100.0));
+        let network = Network {
+            id: "test".to_string(),
+            label: "Test".to_string(),
+            nodes: vec![NodeData::Branch(BranchNode {
+                base: NodeBase {
+                    id: "branch-1".to_string(),
+                    type_: "branch".to_string(),
+                    label: None,
+                    position: Position { x: 0.0, y: 0.0 },
+                    parent_id: None,
+                    width: None,
+                    height: None,
+                    extra: HashMap::new(),
+                },
+                outgoing: vec![],
+                blocks: vec![Block {
+                    quantity: Some(1),
+                    type_: "Compressor".to_string(),
+                    extra,
+                }],
+            })],
+            edges: vec![],
+        };
+
+        let registry = PropertyRegistry::new(config).with_network(&network);
+
+        let temperature = registry.get("ambientTemperature").unwrap();
+        assert_eq!(temperature.dimension.as_deref(), Some("temperature"));
+        assert_eq!(temperature.default, Some(Value::Float(20.0)));
+        assert_eq!(temperature.rule, RuleKind::Property);
+        assert_eq!(
+            temperature.scopes,
+            vec![ScopeLevel::Group, ScopeLevel::Global]
+        );
+
+        let roughness = registry.get("roughness").unwrap();
+        assert_eq!(roughness.overrides["Pipe"], vec![ScopeLevel::Block]);
+        assert!(roughness.block_types.contains("Pipe"));
+
+        let length = registry.get("length").unwrap();
+        assert_eq!(length.rule, RuleKind::General);
+        assert_eq!(
+            registry
+                .for_block_type("Compressor")
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["length"]
+        );
+    }
 }
@@ -1,11 +1,196 @@
 use crate::parser::models::*;
 use crate::scope::config::*;
+use crate::scope::materialize::is_metadata_key;
+use crate::scope::upstream::UpstreamSource;
+use serde::Serialize;
 use toml::Value;
 
 pub struct ScopeResolver {
     config: Config,
 }
 
+/// A block and its surroundings, as needed to resolve its properties
+#[derive(Debug, Clone)]
+pub struct BlockContext<'a> {
+    pub block: &'a Block,
+    pub branch: &'a BranchNode,
+    /// Enclosing groups, nearest first
+    pub groups: Vec<&'a GroupNode>,
+    /// Network and the block's index in its branch, needed for the upstream scope
+    pub network: Option<(&'a Network, usize)>,
+}
+
+impl<'a> BlockContext<'a> {
+    /// A block on its own: the group and upstream scopes don't apply
+    pub fn detached(block: &'a Block, branch: &'a BranchNode) -> Self {
+        Self {
+            block,
+            branch,
+            groups: Vec::new(),
+            network: None,
+        }
+    }
+
+    /// The block at `block_index` in `branch`, with its enclosing groups and upstream network
+    pub fn in_network(
+        network: &'a Network,
+        branch: &'a BranchNode,
+        block_index: usize,
+    ) -> Result<Self, ResolveError> {
+        let block = branch
+            .blocks
+            .get(block_index)
+            .ok_or_else(|| ResolveError::BlockNotFound {
+                branch: branch.base.id.clone(),
+                index: block_index,
+            })?;
+        let groups = network.group_ancestors(branch.base.parent_id.as_deref())?;
+
+        Ok(Self {
+            block,
+            branch,
+            groups,
+            network: Some((network, block_index)),
+        })
+    }
+}
+
+/// Why a property could not be resolved
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    ParentCycle(ParentCycleError),
+    BlockNotFound {
+        branch: String,
+        index: usize,
+    },
+    /// Incoming branches supply different values and upstreamMerge is "error"
+    UpstreamConflict {
+        property: String,
+        node: String,
+        sources: Vec<String>,
+    },
+    /// Incoming values can't be blended (e.g. differing strings)
+    UpstreamBlend {
+        property: String,
+        node: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::ParentCycle(e) => write!(f, "{}", e),
+            ResolveError::BlockNotFound { branch, index } => {
+                write!(f, "Block index {} out of range for '{}'", index, branch)
+            }
+            ResolveError::UpstreamConflict {
+                property,
+                node,
+                sources,
+            } => write!(
+                f,
+                "Upstream values of '{}' disagree at '{}' (from {})",
+                property,
+                node,
+                sources.join(", ")
+            ),
+            ResolveError::UpstreamBlend {
+                property,
+                node,
+                reason,
+            } => write!(
+                f,
+                "Cannot blend upstream values of '{}' at '{}': {}",
+                property, node, reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+impl From<ParentCycleError> for ResolveError {
+    fn from(e: ParentCycleError) -> Self {
+        ResolveError::ParentCycle(e)
+    }
+}
+
+/// Which inheritance rule selected the scope chain for a property
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ChainRule {
+    /// `[inheritance] general`
+    General,
+    /// `[inheritance.rules] <property>`
+    Property,
+    /// `[inheritance.rules.<property>.overrides] <BlockType>`
+    BlockTypeOverride {
+        #[serde(rename = "blockType")]
+        block_type: String,
+    },
+    /// Scopes given explicitly by the caller (e.g. `?scope=block,branch`)
+    Explicit,
+}
+
+/// One scope checked while resolving a property
+#[derive(Debug, Clone, Serialize)]
+pub struct ScopeStep {
+    pub scope: ScopeLevel,
+    /// ID of the node holding this scope (None for global, or when the scope doesn't apply)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    /// False when the scope doesn't exist for this block (e.g. branch has no parent group)
+    pub available: bool,
+    /// Value found at this scope, if any
+    pub value: Option<Value>,
+    /// Blocks an upstream value came from
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<UpstreamSource>,
+    /// Whether this scope supplied the resolved value
+    pub selected: bool,
+    /// Whether this scope filled in fields of a deep-merged table (besides the selected one)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub merged: bool,
+}
+
+/// Where one leaf of a deep-merged table came from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LeafSource {
+    /// Dotted path within the property (e.g. "factors.contingency")
+    pub path: String,
+    pub scope: ScopeLevel,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+}
+
+/// Full account of how a property was resolved for a block
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionTrace {
+    pub property: String,
+    pub block_type: String,
+    pub rule: ChainRule,
+    pub chain: Vec<ScopeLevel>,
+    pub steps: Vec<ScopeStep>,
+    pub value: Option<Value>,
+    pub scope: Option<ScopeLevel>,
+    /// Provenance of each leaf when the value is a deep-merged table
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub leaves: Vec<LeafSource>,
+}
+
+/// What a single node (or config table) holds for a scope
+struct Holder {
+    node: Option<String>,
+    available: bool,
+    value: Option<Value>,
+    sources: Vec<UpstreamSource>,
+}
+
+/// Node property holding display unit preferences, shaped like config.toml [unitPreferences]
+pub const UNIT_PREFERENCES_PROPERTY: &str = "unitPreferences";
+
 impl ScopeResolver {
     pub fn new(config: Config) -> Self {
         Self { config }
@@ -14,79 +199,241 @@ impl ScopeResolver {
     pub fn resolve_property(
         &self,
         property: &str,
-        block: &Block,
-        branch: &BranchNode,
-        group: Option<&GroupNode>,
-    ) -> Option<Value> {
-        self.resolve_property_with_scope(property, block, branch, group)
-            .map(|(value, _)| value)
+        ctx: &BlockContext,
+    ) -> Result<Option<Value>, ResolveError> {
+        Ok(self
+            .resolve_property_with_scope(property, ctx)?
+            .map(|(value, _)| value))
     }
 
     pub fn resolve_property_with_scope(
         &self,
         property: &str,
-        block: &Block,
-        branch: &BranchNode,
-        group: Option<&GroupNode>,
-    ) -> Option<(Value, ScopeLevel)> {
-        let scope_chain = self.get_scope_chain(property, &block.type_);
-        self.resolve_property_with_explicit_scopes(property, block, branch, group, &scope_chain)
+        ctx: &BlockContext,
+    ) -> Result<Option<(Value, ScopeLevel)>, ResolveError> {
+        let scope_chain = self.get_scope_chain(property, &ctx.block.type_);
+        self.resolve_property_with_explicit_scopes(property, ctx, &scope_chain)
     }
 
     pub fn resolve_property_with_explicit_scopes(
         &self,
         property: &str,
-        block: &Block,
-        branch: &BranchNode,
-        group: Option<&GroupNode>,
+        ctx: &BlockContext,
         explicit_scopes: &[ScopeLevel],
-    ) -> Option<(Value, ScopeLevel)> {
+    ) -> Result<Option<(Value, ScopeLevel)>, ResolveError> {
+        // Deep merges need every scope's value, which the trace collects
+        if self.merge_mode(property) == MergeMode::Deep {
+            let trace = self.explain_with_explicit_scopes(property, ctx, explicit_scopes)?;
+            return Ok(trace.value.zip(trace.scope));
+        }
+
         // Walk up the chain until value found
         for scope in explicit_scopes {
-            match scope {
-                ScopeLevel::Block => {
-                    if let Some(v) = block.extra.get(property) {
-                        return Some((v.clone(), ScopeLevel::Block));
-                    }
-                }
-                ScopeLevel::Branch => {
-                    if let Some(v) = branch.base.extra.get(property) {
-                        return Some((v.clone(), ScopeLevel::Branch));
-                    }
-                }
-                ScopeLevel::Group => {
-                    if let Some(v) = group.and_then(|g| g.base.extra.get(property)) {
-                        return Some((v.clone(), ScopeLevel::Group));
-                    }
-                }
-                ScopeLevel::Global => {
-                    // Check config.toml [properties] section
-                    if let Some(v) = self.config.properties.get(property) {
-                        return Some((v.clone(), ScopeLevel::Global));
-                    }
+            let holders = self.holders_at_scope(*scope, property, ctx)?;
+            if let Some(value) = holders.into_iter().find_map(|h| h.value) {
+                return Ok(Some((value, *scope)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Display unit preferences set on the block's nodes (`[unitPreferences]` on the
+    /// block, its branch or groups), resolved through the scope chain of the
+    /// `unitPreferences` property. Tables merge entry by entry, nearest scope first
+    pub fn resolve_unit_preferences(
+        &self,
+        ctx: &BlockContext,
+    ) -> Result<Option<toml::Table>, ResolveError> {
+        let trace = self.explain(UNIT_PREFERENCES_PROPERTY, ctx)?;
+
+        let mut merged = toml::Table::new();
+        let mut leaves = Vec::new();
+        for step in &trace.steps {
+            if let Some(Value::Table(table)) = &step.value {
+                fill_missing(&mut merged, table, "", step, &mut leaves);
+            }
+        }
+        Ok((!merged.is_empty()).then_some(merged))
+    }
+
+    /// Explain how a property resolves for a block: the rule that selected the
+    /// scope chain, what each scope holds, and which one won
+    pub fn explain(
+        &self,
+        property: &str,
+        ctx: &BlockContext,
+    ) -> Result<ResolutionTrace, ResolveError> {
+        let (chain, rule) = self.select_scope_chain(property, &ctx.block.type_);
+        self.explain_with_chain(property, ctx, chain, rule)
+    }
+
+    /// Like `explain`, but for an explicit scope chain
+    pub fn explain_with_explicit_scopes(
+        &self,
+        property: &str,
+        ctx: &BlockContext,
+        explicit_scopes: &[ScopeLevel],
+    ) -> Result<ResolutionTrace, ResolveError> {
+        self.explain_with_chain(property, ctx, explicit_scopes.to_vec(), ChainRule::Explicit)
+    }
+
+    fn explain_with_chain(
+        &self,
+        property: &str,
+        ctx: &BlockContext,
+        chain: Vec<ScopeLevel>,
+        rule: ChainRule,
+    ) -> Result<ResolutionTrace, ResolveError> {
+        let mut steps = Vec::with_capacity(chain.len());
+        let mut resolved: Option<(Value, ScopeLevel)> = None;
+
+        for scope in &chain {
+            let holders = match self.holders_at_scope(*scope, property, ctx) {
+                Ok(holders) => holders,
+                Err(e) if resolved.is_none() => return Err(e),
+                // A scope that would fail doesn't matter once an earlier one resolved
+                Err(_) => vec![Holder {
+                    node: None,
+                    available: true,
+                    value: None,
+                    sources: Vec::new(),
+                }],
+            };
+
+            for holder in holders {
+                let selected = resolved.is_none() && holder.value.is_some();
+                if selected {
+                    resolved = holder.value.clone().map(|v| (v, *scope));
                 }
+                steps.push(ScopeStep {
+                    scope: *scope,
+                    node: holder.node,
+                    available: holder.available,
+                    value: holder.value,
+                    sources: holder.sources,
+                    selected,
+                    merged: false,
+                });
+            }
+        }
+
+        let (mut value, scope) = match resolved {
+            Some((v, s)) => (Some(v), Some(s)),
+            None => (None, None),
+        };
+
+        // Fill in fields missing from the nearest table from farther scopes
+        let mut leaves = Vec::new();
+        if self.merge_mode(property) == MergeMode::Deep && matches!(value, Some(Value::Table(_))) {
+            let mut merged = toml::Table::new();
+            for step in &mut steps {
+                let Some(Value::Table(table)) = &step.value else {
+                    continue;
+                };
+                let contributed = fill_missing(&mut merged, table, "", step, &mut leaves);
+                step.merged = contributed && !step.selected;
             }
+            value = Some(Value::Table(merged));
         }
-        None
+
+        Ok(ResolutionTrace {
+            property: property.to_string(),
+            block_type: ctx.block.type_.clone(),
+            rule,
+            chain,
+            steps,
+            value,
+            scope,
+            leaves,
+        })
+    }
+
+    /// What each node in a scope holds for a property
+    /// Group scope expands to one holder per ancestor group, nearest first
+    fn holders_at_scope(
+        &self,
+        scope: ScopeLevel,
+        property: &str,
+        ctx: &BlockContext,
+    ) -> Result<Vec<Holder>, ResolveError> {
+        let holder = |node: Option<&str>, available: bool, value: Option<&Value>| Holder {
+            node: node.map(|n| n.to_string()),
+            available,
+            value: value.cloned(),
+            sources: Vec::new(),
+        };
+
+        let holders = match scope {
+            ScopeLevel::Block => vec![holder(
+                Some(&ctx.branch.base.id),
+                true,
+                ctx.block.extra.get(property),
+            )],
+            ScopeLevel::Branch => vec![holder(
+                Some(&ctx.branch.base.id),
+                true,
+                ctx.branch.base.extra.get(property),
+            )],
+            ScopeLevel::Group if ctx.groups.is_empty() => vec![holder(None, false, None)],
+            ScopeLevel::Group => ctx
+                .groups
+                .iter()
+                .map(|g| holder(Some(&g.base.id), true, g.base.extra.get(property)))
+                .collect(),
+            ScopeLevel::BlockType => vec![holder(
+                None,
+                self.config.block_defaults.contains_key(&ctx.block.type_),
+                self.block_default(&ctx.block.type_, property),
+            )],
+            ScopeLevel::Upstream => match ctx.network {
+                None => vec![holder(None, false, None)],
+                Some((network, block_index)) => {
+                    let merge = self.upstream_merge(property);
+                    match network.upstream_value(property, ctx.branch, block_index, merge)? {
+                        Some((value, sources)) => vec![Holder {
+                            node: sources.first().map(|s| s.node.clone()),
+                            available: true,
+                            value: Some(value),
+                            sources,
+                        }],
+                        None => vec![holder(None, true, None)],
+                    }
+                }
+            },
+            // Check config.toml [properties] section
+            ScopeLevel::Global => vec![holder(None, true, self.config.properties.get(property))],
+        };
+
+        Ok(holders)
     }
 
     fn get_scope_chain(&self, property: &str, block_type: &str) -> Vec<ScopeLevel> {
-        // Get inheritance rule for property, or use general default
+        self.select_scope_chain(property, block_type).0
+    }
+
+    /// Get inheritance chain for property, or use general default, along with the rule that chose it
+    fn select_scope_chain(&self, property: &str, block_type: &str) -> (Vec<ScopeLevel>, ChainRule) {
         let rule = self.config.inheritance.rules.get(property);
 
         match rule {
-            Some(PropertyInheritanceRule::Simple(scopes)) => scopes.clone(),
+            Some(PropertyInheritanceRule::Simple(scopes)) => (scopes.clone(), ChainRule::Property),
             Some(PropertyInheritanceRule::Complex {
                 inheritance,
                 overrides,
+                ..
             }) => {
                 // Check for block-type override
-                overrides
-                    .get(block_type)
-                    .cloned()
-                    .unwrap_or_else(|| inheritance.clone())
+                match overrides.get(block_type) {
+                    Some(scopes) => (
+                        scopes.clone(),
+                        ChainRule::BlockTypeOverride {
+                            block_type: block_type.to_string(),
+                        },
+                    ),
+                    None => (inheritance.clone(), ChainRule::Property),
+                }
             }
-            None => self.config.inheritance.general.clone(),
+            None => (self.config.inheritance.general.clone(), ChainRule::General),
         }
     }
 
@@ -111,7 +458,300 @@ impl ScopeResolver {
         }
     }
 
+    /// How values of a property found at several scopes combine
+    pub fn merge_mode(&self, property: &str) -> MergeMode {
+        match self.config.inheritance.rules.get(property) {
+            Some(PropertyInheritanceRule::Complex { merge, .. }) => *merge,
+            _ => MergeMode::Replace,
+        }
+    }
+
+    /// Merge behaviour of the upstream scope for a property
+    pub fn upstream_merge(&self, property: &str) -> UpstreamMerge {
+        match self.config.inheritance.rules.get(property) {
+            Some(PropertyInheritanceRule::Complex {
+                upstream_merge: Some(merge),
+                ..
+            }) => *merge,
+            _ => self.config.inheritance.upstream_merge,
+        }
+    }
+
+    /// Whether any inheritance chain (general, per-property or override) includes a scope
+    pub fn uses_scope(&self, scope: ScopeLevel) -> bool {
+        let inheritance = &self.config.inheritance;
+        inheritance.general.contains(&scope)
+            || inheritance.rules.values().any(|rule| match rule {
+                PropertyInheritanceRule::Simple(scopes) => scopes.contains(&scope),
+                PropertyInheritanceRule::Complex {
+                    inheritance,
+                    overrides,
+                    ..
+                } => {
+                    inheritance.contains(&scope)
+                        || overrides.values().any(|scopes| scopes.contains(&scope))
+                }
+            })
+    }
+
     pub fn has_global_property(&self, property: &str) -> bool {
         self.config.properties.contains_key(property)
     }
+
+    /// Value of a key in config.toml [properties] (including `_<property>_original` metadata)
+    pub fn global_property(&self, name: &str) -> Option<&Value> {
+        self.config.properties.get(name)
+    }
+
+    /// Names of all properties defined in config.toml [properties]
+    pub fn global_property_names(&self) -> Vec<&String> {
+        self.config
+            .properties
+            .keys()
+            .filter(|k| !is_metadata_key(k))
+            .collect()
+    }
+
+    /// Default for a property from config.toml [blockDefaults.<block_type>]
+    pub fn block_default(&self, block_type: &str, property: &str) -> Option<&Value> {
+        self.config
+            .block_defaults
+            .get(block_type)
+            .and_then(|defaults| defaults.get(property))
+    }
+
+    /// Names of all properties defined in config.toml [blockDefaults.<block_type>]
+    pub fn block_default_names(&self, block_type: &str) -> Vec<&String> {
+        self.config
+            .block_defaults
+            .get(block_type)
+            .map(|defaults| defaults.keys().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Copy fields of `source` that `target` lacks, recursing into tables both have
+/// Returns whether anything was copied; each copied leaf is attributed to `step`
+fn fill_missing(
+    target: &mut toml::Table,
+    source: &toml::Table,
+    prefix: &str,
+    step: &ScopeStep,
+    leaves: &mut Vec<LeafSource>,
+) -> bool {
+    let mut contributed = false;
+    for (key, value) in source {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (target.get_mut(key), value) {
+            (Some(Value::Table(existing)), Value::Table(nested)) => {
+                contributed |= fill_missing(existing, nested, &path, step, leaves);
+            }
+            // Nearer scopes win, even over a table
+            (Some(_), _) => {}
+            (None, value) => {
+                record_leaves(value, &path, step, leaves);
+                target.insert(key.clone(), value.clone());
+                contributed = true;
+            }
+        }
+    }
+    contributed
+}
+
+fn record_leaves(value: &Value, path: &str, step: &ScopeStep, leaves: &mut Vec<LeafSource>) {
+    match value {
+        Value::Table(table) if !table.is_empty() => {
+            for (key, nested) in table {
+                record_leaves(nested, &format!("{}.{}", path, key), step, leaves);
+            }
+        }
+        _ => leaves.push(LeafSource {
+            path: path.to_string(),
+            scope: step.scope,
+            node: step.node.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn branch_with_block(block_type: &str) -> BranchNode {
+        let mut extra = HashMap::new();
+        extra.insert("roughness".to_string(), Value::Float(0.05));
+        BranchNode {
+            base: NodeBase {
+                id: "branch-1".to_string(),
+                type_: "branch".to_string(),
+                label: None,
+                position: Position { x: 0.0, y: 0.0 },
+                parent_id: None,
+                width: None,
+                height: None,
+                extra,
+            },
+            outgoing: vec![],
+            blocks: vec![Block {
+                quantity: Some(1),
+                type_: block_type.to_string(),
+                extra: HashMap::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_explain_reports_override_rule_and_each_scope() {
+        let config = Config::load_from_str(
+            r#"
+[properties]
+roughness = 0.045
+
+[inheritance.rules.roughness]
+inheritance = ["block", "branch", "global"]
+overrides = { Pipe = ["block", "group", "global"] }
+"#,
+        )
+        .unwrap();
+        let resolver = ScopeResolver::new(config);
+
+        let branch = branch_with_block("Pipe");
+        let trace = resolver
+            .explain(
+                "roughness",
+                &BlockContext::detached(&branch.blocks[0], &branch),
+            )
+            .unwrap();
+
+        assert_eq!(
+            trace.rule,
+            ChainRule::BlockTypeOverride {
+                block_type: "Pipe".to_string()
+            }
+        );
+        assert_eq!(trace.steps.len(), 3);
+        assert!(trace.steps[0].value.is_none());
+        assert!(!trace.steps[1].available);
+        assert!(trace.steps[2].selected);
+        assert_eq!(trace.scope, Some(ScopeLevel::Global));
+        assert_eq!(trace.value, Some(Value::Float(0.045)));
+
+        // Without the override the branch value wins
+        let branch = branch_with_block("Compressor");
+        let trace = resolver
+            .explain(
+                "roughness",
+                &BlockContext::detached(&branch.blocks[0], &branch),
+            )
+            .unwrap();
+        assert_eq!(trace.rule, ChainRule::Property);
+        assert_eq!(trace.scope, Some(ScopeLevel::Branch));
+        assert!(trace.steps[1].selected && !trace.steps[2].selected);
+    }
+
+    #[test]
+    fn test_block_type_defaults_scope() {
+        let config = Config::load_from_str(
+            r#"
+[properties]
+roughness = 0.01
+
+[blockDefaults.Pipe]
+roughness = 0.045
+
+[inheritance]
+general = ["block", "blockType", "global"]
+"#,
+        )
+        .unwrap();
+        let resolver = ScopeResolver::new(config);
+
+        let pipe = branch_with_block("Pipe");
+        let resolved = resolver
+            .resolve_property_with_scope(
+                "roughness",
+                &BlockContext::detached(&pipe.blocks[0], &pipe),
+            )
+            .unwrap();
+        assert_eq!(resolved, Some((Value::Float(0.045), ScopeLevel::BlockType)));
+
+        // No defaults table for this type: the scope is reported as not applicable
+        let compressor = branch_with_block("Compressor");
+        let trace = resolver
+            .explain(
+                "roughness",
+                &BlockContext::detached(&compressor.blocks[0], &compressor),
+            )
+            .unwrap();
+        assert!(!trace.steps[1].available);
+        assert_eq!(trace.scope, Some(ScopeLevel::Global));
+    }
+
+    #[test]
+    fn test_deep_merge_keeps_fields_from_farther_scopes() {
+        let config = Config::load_from_str(
+            r#"
+[properties.costing]
+currency = "EUR"
+factors = { contingency = 0.1, overhead = 0.2 }
+
+[inheritance.rules.costing]
+inheritance = ["block", "branch", "global"]
+merge = "deep"
+"#,
+        )
+        .unwrap();
+        let resolver = ScopeResolver::new(config);
+
+        let mut branch = branch_with_block("Pipe");
+        branch.blocks[0].extra.insert(
+            "costing".to_string(),
+            toml::from_str("factors = { contingency = 0.3 }").unwrap(),
+        );
+
+        let trace = resolver
+            .explain(
+                "costing",
+                &BlockContext::detached(&branch.blocks[0], &branch),
+            )
+            .unwrap();
+        let value = trace.value.unwrap();
+        assert_eq!(value["currency"].as_str(), Some("EUR"));
+        assert_eq!(value["factors"]["contingency"].as_float(), Some(0.3));
+        assert_eq!(value["factors"]["overhead"].as_float(), Some(0.2));
+        assert_eq!(trace.scope, Some(ScopeLevel::Block));
+        assert!(trace.steps[2].merged);
+
+        let leaf = |path: &str| trace.leaves.iter().find(|l| l.path == path).unwrap().scope;
+        assert_eq!(leaf("factors.contingency"), ScopeLevel::Block);
+        assert_eq!(leaf("factors.overhead"), ScopeLevel::Global);
+        assert_eq!(leaf("currency"), ScopeLevel::Global);
+    }
+
+    #[test]
+    fn test_unit_preferences_merge_nearest_first() {
+        let resolver = ScopeResolver::new(Config::empty());
+
+        let mut branch = branch_with_block("Pipe");
+        branch.base.extra.insert(
+            UNIT_PREFERENCES_PROPERTY.to_string(),
+            toml::from_str("dimensions = { length = \"km\", pressure = \"barg\" }").unwrap(),
+        );
+        branch.blocks[0].extra.insert(
+            UNIT_PREFERENCES_PROPERTY.to_string(),
+            toml::from_str("dimensions = { length = \"mi\" }").unwrap(),
+        );
+
+        let preferences = resolver
+            .resolve_unit_preferences(&BlockContext::detached(&branch.blocks[0], &branch))
+            .unwrap()
+            .unwrap();
+        assert_eq!(preferences["dimensions"]["length"].as_str(), Some("mi"));
+        assert_eq!(preferences["dimensions"]["pressure"].as_str(), Some("barg"));
+    }
 }
@@ -0,0 +1,431 @@
+// Flow-propagated ("upstream") property lookup
+
+use crate::parser::models::*;
+use crate::scope::config::UpstreamMerge;
+use crate::scope::resolver::ResolveError;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use toml::Value;
+
+/// A block that supplied (part of) an upstream value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamSource {
+    pub node: String,
+    pub block_index: usize,
+    /// Share of the value contributed by this block (1.0 unless blended)
+    pub weight: f64,
+}
+
+type Found = (Value, Vec<UpstreamSource>);
+
+impl Network {
+    /// Nearest value of `property` set on a block upstream of `branch.blocks[block_index]`
+    /// Earlier blocks in the same branch are checked first (nearest first), then the
+    /// branches feeding into it via edges, each walked back the same way
+    pub fn upstream_value(
+        &self,
+        property: &str,
+        branch: &BranchNode,
+        block_index: usize,
+        merge: UpstreamMerge,
+    ) -> Result<Option<Found>, ResolveError> {
+        let mut walk = Walk {
+            property,
+            merge,
+            path: HashSet::from([branch.base.id.as_str()]),
+            memo: HashMap::new(),
+        };
+        let (found, _) = self.upstream_from(branch, block_index, &mut walk)?;
+        Ok(found)
+    }
+
+    /// Value found upstream of `branch.blocks[end]`, and whether a loop in the
+    /// flow was cut short on the way
+    fn upstream_from<'a>(
+        &'a self,
+        branch: &'a BranchNode,
+        end: usize,
+        walk: &mut Walk<'a>,
+    ) -> Result<(Option<Found>, bool), ResolveError> {
+        let end = end.min(branch.blocks.len());
+        for (index, block) in branch.blocks[..end].iter().enumerate().rev() {
+            if let Some(value) = block.extra.get(walk.property) {
+                let source = UpstreamSource {
+                    node: branch.base.id.clone(),
+                    block_index: index,
+                    weight: 1.0,
+                };
+                return Ok((Some((value.clone(), vec![source])), false));
+            }
+        }
+
+        let mut incoming: Vec<(Found, u32)> = Vec::new();
+        let mut cut = false;
+        for edge in self.edges.iter().filter(|e| e.target == branch.base.id) {
+            let Some(NodeData::Branch(source)) = self.find_node(&edge.source) else {
+                continue;
+            };
+            let id = source.base.id.as_str();
+            if let Some(found) = walk.memo.get(id) {
+                if let Some(found) = found {
+                    incoming.push((found.clone(), edge.data.weight));
+                }
+                continue;
+            }
+            // Loops in the flow are walked once per path
+            if !walk.path.insert(id) {
+                cut = true;
+                continue;
+            }
+            let walked = self.upstream_from(source, source.blocks.len(), walk);
+            walk.path.remove(id);
+
+            let (found, source_cut) = walked?;
+            // A result that skipped part of a loop depends on the path it was reached by
+            if source_cut {
+                cut = true;
+            } else {
+                walk.memo.insert(id, found.clone());
+            }
+            if let Some(found) = found {
+                incoming.push((found, edge.data.weight));
+            }
+        }
+
+        let found = merge_incoming(walk.property, &branch.base.id, incoming, walk.merge)?;
+        Ok((found, cut))
+    }
+}
+
+/// State of one upstream lookup
+struct Walk<'a> {
+    property: &'a str,
+    merge: UpstreamMerge,
+    /// Branches on the path currently being walked
+    path: HashSet<&'a str>,
+    /// Value upstream of the end of each fully walked branch, so a branch reached
+    /// over several paths (e.g. diamonds in the flow) is only walked once
+    memo: HashMap<&'a str, Option<Found>>,
+}
+
+/// Combine the values arriving over several incoming edges
+fn merge_incoming(
+    property: &str,
+    node: &str,
+    incoming: Vec<(Found, u32)>,
+    merge: UpstreamMerge,
+) -> Result<Option<Found>, ResolveError> {
+    let Some(((first_value, first_sources), _)) = incoming.first() else {
+        return Ok(None);
+    };
+
+    let agree = incoming.iter().all(|((value, _), _)| value == first_value);
+    if incoming.len() == 1 || (agree && merge != UpstreamMerge::Blend) {
+        return Ok(Some((first_value.clone(), first_sources.clone())));
+    }
+
+    match merge {
+        UpstreamMerge::FirstWins => Ok(Some((first_value.clone(), first_sources.clone()))),
+        UpstreamMerge::Error => Err(ResolveError::UpstreamConflict {
+            property: property.to_string(),
+            node: node.to_string(),
+            sources: incoming
+                .iter()
+                .flat_map(|((_, sources), _)| sources)
+                .map(|s| format!("{}/blocks/{}", s.node, s.block_index))
+                .collect(),
+        }),
+        UpstreamMerge::Blend => {
+            // Edges without weights count equally
+            let total: u32 = incoming.iter().map(|(_, weight)| weight).sum();
+            let share = |weight: u32| {
+                if total == 0 {
+                    1.0 / incoming.len() as f64
+                } else {
+                    weight as f64 / total as f64
+                }
+            };
+
+            let weighted: Vec<(&Value, f64)> = incoming
+                .iter()
+                .map(|((value, _), weight)| (value, share(*weight)))
+                .collect();
+            let value = blend(&weighted).map_err(|reason| ResolveError::UpstreamBlend {
+                property: property.to_string(),
+                node: node.to_string(),
+                reason,
+            })?;
+
+            // A block reached over several paths is listed once, with its shares summed
+            let mut sources: Vec<UpstreamSource> = Vec::new();
+            for ((_, found), weight) in &incoming {
+                let share = share(*weight);
+                for source in found {
+                    match sources
+                        .iter_mut()
+                        .find(|s| s.node == source.node && s.block_index == source.block_index)
+                    {
+                        Some(existing) => existing.weight += source.weight * share,
+                        None => sources.push(UpstreamSource {
+                            weight: source.weight * share,
+                            ..source.clone()
+                        }),
+                    }
+                }
+            }
+
+            Ok(Some((value, sources)))
+        }
+    }
+}
+
+/// Weighted average of numbers; tables are blended key by key, with a missing key
+/// counting as zero (so compositions blend naturally); other values must agree
+fn blend(values: &[(&Value, f64)]) -> Result<Value, String> {
+    if values
+        .iter()
+        .all(|(v, _)| matches!(v, Value::Integer(_) | Value::Float(_)))
+    {
+        let sum = values
+            .iter()
+            .map(|(v, share)| match v {
+                Value::Integer(i) => *i as f64 * share,
+                Value::Float(f) => f * share,
+                _ => 0.0,
+            })
+            .sum();
+        return Ok(Value::Float(sum));
+    }
+
+    if values.iter().all(|(v, _)| v.is_table()) {
+        let keys: BTreeSet<&String> = values
+            .iter()
+            .filter_map(|(v, _)| v.as_table())
+            .flat_map(|t| t.keys())
+            .collect();
+
+        let zero = Value::Integer(0);
+        let mut table = toml::map::Map::new();
+        for key in keys {
+            let column: Vec<(&Value, f64)> = values
+                .iter()
+                .map(|(v, share)| (v.get(key.as_str()).unwrap_or(&zero), *share))
+                .collect();
+            let blended = blend(&column).map_err(|reason| format!("{}: {}", key, reason))?;
+            table.insert(key.clone(), blended);
+        }
+        return Ok(Value::Table(table));
+    }
+
+    let first = values[0].0;
+    if values.iter().all(|(v, _)| *v == first) {
+        Ok(first.clone())
+    } else {
+        Err(format!(
+            "{} and {} can't be averaged",
+            first,
+            values
+                .iter()
+                .map(|(v, _)| *v)
+                .find(|v| *v != first)
+                .unwrap_or(first)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn branch(id: &str, outgoing: &[(&str, u32)], blocks: Vec<Block>) -> BranchNode {
+        BranchNode {
+            base: NodeBase {
+                id: id.to_string(),
+                type_: "branch".to_string(),
+                label: None,
+                position: Position { x: 0.0, y: 0.0 },
+                parent_id: None,
+                width: None,
+                height: None,
+                extra: HashMap::new(),
+            },
+            outgoing: outgoing
+                .iter()
+                .map(|(target, weight)| Outgoing {
+                    target: target.to_string(),
+                    weight: *weight,
+                })
+                .collect(),
+            blocks,
+        }
+    }
+
+    fn block(type_: &str, properties: &[(&str, Value)]) -> Block {
+        Block {
+            quantity: Some(1),
+            type_: type_.to_string(),
+            extra: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        }
+    }
+
+    /// Two sources feeding one pipe branch with weights 3:1
+    fn merge_network(purity_a: f64, purity_b: f64) -> Network {
+        let branches = vec![
+            branch(
+                "source-a",
+                &[("pipe", 3)],
+                vec![block("Source", &[("purity", Value::Float(purity_a))])],
+            ),
+            branch(
+                "source-b",
+                &[("pipe", 1)],
+                vec![block("Source", &[("purity", Value::Float(purity_b))])],
+            ),
+            branch("pipe", &[], vec![block("Pipe", &[]), block("Pipe", &[])]),
+        ];
+        network_of(branches)
+    }
+
+    fn network_of(branches: Vec<BranchNode>) -> Network {
+        let edges = branches
+            .iter()
+            .flat_map(|b| {
+                b.outgoing.iter().map(|o| Edge {
+                    id: format!("{}-{}", b.base.id, o.target),
+                    source: b.base.id.clone(),
+                    target: o.target.clone(),
+                    data: EdgeData { weight: o.weight },
+                })
+            })
+            .collect();
+
+        Network {
+            id: "test".to_string(),
+            label: "Test".to_string(),
+            nodes: branches.into_iter().map(NodeData::Branch).collect(),
+            edges,
+        }
+    }
+
+    fn pipe_branch(network: &Network) -> &BranchNode {
+        match network.find_node("pipe") {
+            Some(NodeData::Branch(b)) => b,
+            _ => panic!("Expected pipe branch"),
+        }
+    }
+
+    #[test]
+    fn test_upstream_merge_modes() {
+        let network = merge_network(0.96, 0.92);
+        let pipe = pipe_branch(&network);
+
+        let (value, sources) = network
+            .upstream_value("purity", pipe, 1, UpstreamMerge::FirstWins)
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, Value::Float(0.96));
+        assert_eq!(sources[0].node, "source-a");
+
+        let err = network
+            .upstream_value("purity", pipe, 1, UpstreamMerge::Error)
+            .unwrap_err();
+        assert!(matches!(err, ResolveError::UpstreamConflict { .. }));
+
+        let (value, sources) = network
+            .upstream_value("purity", pipe, 1, UpstreamMerge::Blend)
+            .unwrap()
+            .unwrap();
+        let blended = value.as_float().unwrap();
+        assert!((blended - 0.95).abs() < 1e-9);
+        assert_eq!(sources.len(), 2);
+        assert!((sources[0].weight - 0.75).abs() < 1e-9);
+
+        // Sources that agree are not a conflict
+        let network = merge_network(0.96, 0.96);
+        let (value, _) = network
+            .upstream_value("purity", pipe_branch(&network), 0, UpstreamMerge::Error)
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, Value::Float(0.96));
+    }
+
+    #[test]
+    fn test_upstream_prefers_earlier_block_in_branch() {
+        let mut network = merge_network(0.96, 0.92);
+        if let Some(NodeData::Branch(pipe)) = network.nodes.iter_mut().find(|n| n.id() == "pipe") {
+            pipe.blocks[0]
+                .extra
+                .insert("purity".to_string(), Value::Float(0.99));
+        }
+        let pipe = pipe_branch(&network);
+
+        let (value, sources) = network
+            .upstream_value("purity", pipe, 1, UpstreamMerge::Error)
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, Value::Float(0.99));
+        assert_eq!(sources[0].block_index, 0);
+
+        // The block itself is not upstream of itself
+        let found = network
+            .upstream_value("purity", pipe, 0, UpstreamMerge::FirstWins)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.0, Value::Float(0.96));
+    }
+
+    #[test]
+    fn test_upstream_walks_diamonds_once() {
+        // 40 diamonds in a row: 2^40 paths from the last join back to the source
+        let mut branches = vec![branch(
+            "join-0",
+            &[("left-1", 1), ("right-1", 1)],
+            vec![block("Source", &[("purity", Value::Float(0.96))])],
+        )];
+        for i in 1..=40 {
+            let join = format!("join-{}", i);
+            let next = [
+                (format!("left-{}", i + 1), 1),
+                (format!("right-{}", i + 1), 1),
+            ];
+            let next: Vec<(&str, u32)> = if i < 40 {
+                next.iter().map(|(id, w)| (id.as_str(), *w)).collect()
+            } else {
+                Vec::new()
+            };
+            branches.push(branch(&format!("left-{}", i), &[(&join, 1)], vec![]));
+            branches.push(branch(&format!("right-{}", i), &[(&join, 1)], vec![]));
+            branches.push(branch(&join, &next, vec![block("Pipe", &[])]));
+        }
+        let network = network_of(branches);
+        let Some(NodeData::Branch(last)) = network.find_node("join-40") else {
+            panic!("Expected join branch");
+        };
+
+        let (value, sources) = network
+            .upstream_value("purity", last, 1, UpstreamMerge::Blend)
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, Value::Float(0.96));
+        assert_eq!(sources.len(), 1);
+        assert!((sources[0].weight - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_blend_tables_key_by_key() {
+        let a: Value = toml::from_str("co2 = 0.9\nn2 = 0.1").unwrap();
+        let b: Value = toml::from_str("co2 = 1.0").unwrap();
+        let blended = blend(&[(&a, 0.5), (&b, 0.5)]).unwrap();
+        assert!((blended["co2"].as_float().unwrap() - 0.95).abs() < 1e-9);
+        assert!((blended["n2"].as_float().unwrap() - 0.05).abs() < 1e-9);
+
+        let s1 = Value::String("dense".to_string());
+        let s2 = Value::String("gas".to_string());
+        assert!(blend(&[(&s1, 0.5), (&s2, 0.5)]).is_err());
+    }
+}
@@ -0,0 +1,661 @@
+// Validation of config.toml itself, with line-precise locations
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dim::{validate_dimension, DimParser};
+use crate::parser::models::*;
+use crate::parser::validation::ValidationResult;
+use crate::schema::registry::SchemaRegistry;
+use crate::scope::config::ScopeLevel;
+use std::collections::HashSet;
+use std::ops::Range;
+use toml::de::{DeTable, DeValue};
+use toml::Spanned;
+
+const UPSTREAM_MERGE_MODES: [&str; 3] = ["firstWins", "error", "blend"];
+const MERGE_MODES: [&str; 2] = ["replace", "deep"];
+
+/// Checks a config.toml for mistakes that deserialization rejects opaquely or ignores:
+/// unknown scope levels, empty chains, malformed rules, overrides and preferences for
+/// block types no schema defines, rules for properties nothing uses, and preferred
+/// units that don't match their dimension
+pub struct ConfigValidator<'a> {
+    network: Option<&'a Network>,
+    registry: Option<&'a SchemaRegistry>,
+}
+
+impl<'a> ConfigValidator<'a> {
+    pub fn new() -> Self {
+        Self {
+            network: None,
+            registry: None,
+        }
+    }
+
+    /// Check that rules refer to properties used in this network
+    pub fn with_network(mut self, network: &'a Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Check block types against every loaded schema library
+    pub fn with_schemas(mut self, registry: &'a SchemaRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Validate config content; `file` prefixes each location ("config.toml:12:5")
+    pub fn validate(&self, content: &str, file: &str) -> ValidationResult {
+        let mut check = Check {
+            content,
+            file,
+            result: ValidationResult::new(),
+        };
+
+        let document = match DeTable::parse(content) {
+            Ok(document) => document,
+            Err(e) => {
+                let location = e.span().map(|span| check.location(&span));
+                check
+                    .result
+                    .add_error("config-parse", e.message().to_string(), location);
+                return check.result;
+            }
+        };
+        let root = document.get_ref();
+
+        let known_block_types = self.schema_block_types();
+        let used_properties = self.used_properties(root);
+
+        if let Some(inheritance) = root.get("inheritance") {
+            self.check_inheritance(
+                &mut check,
+                inheritance,
+                known_block_types.as_ref(),
+                used_properties.as_ref(),
+            );
+        }
+
+        if let Some(preferences) = root.get("unitPreferences") {
+            self.check_unit_preferences(&mut check, root, preferences, known_block_types.as_ref());
+        }
+
+        if let Some(version) = root.get("schemaVersion") {
+            self.check_schema_version(&mut check, version);
+        }
+        if let Some(dir) = root.get("schemasDir") {
+            if dir.get_ref().as_str().is_none() {
+                check.error(
+                    "invalid-value",
+                    "schemasDir must be a path string".to_string(),
+                    dir,
+                );
+            }
+        }
+
+        if let Some(Some(defaults)) = root.get("blockDefaults").map(|d| d.get_ref().as_table()) {
+            for (block_type, _) in defaults {
+                check_block_type(
+                    &mut check,
+                    block_type,
+                    known_block_types.as_ref(),
+                    "[blockDefaults]",
+                );
+            }
+        }
+
+        check.result
+    }
+
+    fn check_schema_version(&self, check: &mut Check, version: &Spanned<DeValue>) {
+        let Some(name) = version.get_ref().as_str() else {
+            check.error(
+                "invalid-value",
+                "schemaVersion must be a string such as \"v1.0\"".to_string(),
+                version,
+            );
+            return;
+        };
+
+        let Some(registry) = self.registry else {
+            return;
+        };
+        let versions = registry.list_versions();
+        if !versions.is_empty() && !versions.iter().any(|v| *v == name) {
+            let mut available: Vec<&str> = versions.iter().map(|v| v.as_str()).collect();
+            available.sort();
+            check.warning(
+                "unknown-schema-version",
+                format!(
+                    "schemaVersion '{}' is not among the schema libraries found ({})",
+                    name,
+                    available.join(", ")
+                ),
+                version,
+            );
+        }
+    }
+
+    fn check_inheritance(
+        &self,
+        check: &mut Check,
+        inheritance: &Spanned<DeValue>,
+        known_block_types: Option<&HashSet<String>>,
+        used_properties: Option<&HashSet<String>>,
+    ) {
+        let Some(inheritance) = inheritance.get_ref().as_table() else {
+            check.error(
+                "invalid-inheritance",
+                "[inheritance] must be a table".to_string(),
+                inheritance,
+            );
+            return;
+        };
+
+        if let Some(general) = inheritance.get("general") {
+            check_chain(check, general, "[inheritance] general");
+        }
+
+        if let Some(merge) = inheritance.get("upstreamMerge") {
+            check_choice(check, merge, "upstreamMerge", &UPSTREAM_MERGE_MODES);
+        }
+
+        let Some(rules) = inheritance.get("rules") else {
+            return;
+        };
+        let Some(rules) = rules.get_ref().as_table() else {
+            check.error(
+                "invalid-inheritance",
+                "[inheritance.rules] must be a table".to_string(),
+                rules,
+            );
+            return;
+        };
+
+        for (property, rule) in rules {
+            let context = format!("rule for '{}'", property.get_ref());
+
+            match rule.get_ref() {
+                DeValue::Array(_) => check_chain(check, rule, &context),
+                DeValue::Table(table) => {
+                    match table.get("inheritance") {
+                        Some(chain) => check_chain(check, chain, &context),
+                        None => check.error(
+                            "invalid-rule",
+                            format!("{} is a table but has no 'inheritance' list", context),
+                            rule,
+                        ),
+                    }
+
+                    if let Some(overrides) = table.get("overrides") {
+                        check_overrides(check, overrides, property.get_ref(), known_block_types);
+                    }
+                    if let Some(merge) = table.get("upstreamMerge") {
+                        check_choice(check, merge, "upstreamMerge", &UPSTREAM_MERGE_MODES);
+                    }
+                    if let Some(merge) = table.get("merge") {
+                        check_choice(check, merge, "merge", &MERGE_MODES);
+                    }
+
+                    for (key, _) in table {
+                        let known = ["inheritance", "overrides", "upstreamMerge", "merge"];
+                        if !known.contains(&key.get_ref().as_ref()) {
+                            check.warning(
+                                "unknown-key",
+                                format!("Unknown key '{}' in {}", key.get_ref(), context),
+                                key,
+                            );
+                        }
+                    }
+                }
+                other => check.error(
+                    "invalid-rule",
+                    format!(
+                        "{} must be a list of scopes or a table with 'inheritance', found {}",
+                        context,
+                        other.type_str()
+                    ),
+                    rule,
+                ),
+            }
+
+            if let Some(used) = used_properties {
+                if !used.contains(property.get_ref().as_ref()) {
+                    check.warning(
+                        "unused-rule",
+                        format!(
+                            "Inheritance rule for '{}', which no node, schema or [properties] entry uses",
+                            property.get_ref()
+                        ),
+                        property,
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_unit_preferences(
+        &self,
+        check: &mut Check,
+        root: &DeTable,
+        preferences: &Spanned<DeValue>,
+        known_block_types: Option<&HashSet<String>>,
+    ) {
+        let Some(preferences) = preferences.get_ref().as_table() else {
+            check.error(
+                "invalid-unit-preferences",
+                "[unitPreferences] must be a table".to_string(),
+                preferences,
+            );
+            return;
+        };
+
+        // Preferred units are only checked where the dim parser is available
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut parser = DimParser::new().ok();
+        #[cfg(not(target_arch = "wasm32"))]
+        let declared_dimensions = root.get("dimensions").and_then(|d| d.get_ref().as_table());
+        #[cfg(target_arch = "wasm32")]
+        let _ = root;
+
+        for (name, entries) in preferences {
+            let Some(entries) = entries.get_ref().as_table() else {
+                check.error(
+                    "invalid-unit-preferences",
+                    format!("[unitPreferences.{}] must be a table", name.get_ref()),
+                    entries,
+                );
+                continue;
+            };
+
+            let by_dimension = name.get_ref() == "dimensions";
+            if !by_dimension {
+                check_block_type(check, name, known_block_types, "[unitPreferences]");
+            }
+
+            for (key, unit) in entries {
+                let Some(unit_str) = unit.get_ref().as_str() else {
+                    check.error(
+                        "invalid-unit-preferences",
+                        format!("Preferred unit for '{}' must be a string", key.get_ref()),
+                        unit,
+                    );
+                    continue;
+                };
+
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    // Dimension of the entry: its key, or the property's schema/declared dimension
+                    let dimension = if by_dimension {
+                        Some(key.get_ref().to_string())
+                    } else {
+                        self.property_dimension(name.get_ref(), key.get_ref())
+                            .or_else(|| {
+                                declared_dimensions
+                                    .and_then(|d| d.get(key.get_ref().as_ref()))
+                                    .and_then(|d| d.get_ref().as_str())
+                                    .map(|d| d.to_string())
+                            })
+                    };
+                    if let Some(parser) = parser.as_mut() {
+                        check_preferred_unit(
+                            check,
+                            parser,
+                            key.get_ref(),
+                            unit,
+                            unit_str,
+                            dimension.as_deref(),
+                        );
+                    }
+                }
+                #[cfg(target_arch = "wasm32")]
+                let _ = unit_str;
+            }
+        }
+    }
+
+    /// Block types defined by any loaded schema library (None without schemas)
+    fn schema_block_types(&self) -> Option<HashSet<String>> {
+        let registry = self.registry?;
+        let types: HashSet<String> = registry
+            .list_versions()
+            .into_iter()
+            .flat_map(|version| registry.list_block_types(version))
+            .cloned()
+            .collect();
+        (!types.is_empty()).then_some(types)
+    }
+
+    /// Dimension a schema gives a block type's property, in any loaded version
+    #[cfg(not(target_arch = "wasm32"))]
+    fn property_dimension(&self, block_type: &str, property: &str) -> Option<String> {
+        let registry = self.registry?;
+        registry.list_versions().into_iter().find_map(|version| {
+            registry
+                .get_schema(version, block_type)
+                .and_then(|schema| schema.properties.get(property))
+                .and_then(|meta| meta.dimension.clone())
+        })
+    }
+
+    /// Every property name set in the network, declared by a schema, or defined in
+    /// [properties] / [blockDefaults] (None without a network)
+    fn used_properties(&self, root: &DeTable) -> Option<HashSet<String>> {
+        let network = self.network?;
+        let mut used = HashSet::new();
+
+        for node in &network.nodes {
+            used.extend(node.base().extra.keys().cloned());
+            if let NodeData::Branch(branch) = node {
+                for block in &branch.blocks {
+                    used.extend(block.extra.keys().cloned());
+                }
+            }
+        }
+
+        if let Some(registry) = self.registry {
+            for version in registry.list_versions() {
+                for block_type in registry.list_block_types(version) {
+                    if let Some(schema) = registry.get_schema(version, block_type) {
+                        used.extend(schema.required_properties.iter().cloned());
+                        used.extend(schema.optional_properties.iter().cloned());
+                        used.extend(schema.properties.keys().cloned());
+                    }
+                }
+            }
+        }
+
+        if let Some(Some(properties)) = root.get("properties").map(|p| p.get_ref().as_table()) {
+            used.extend(properties.keys().map(|k| k.get_ref().to_string()));
+        }
+        if let Some(Some(defaults)) = root.get("blockDefaults").map(|d| d.get_ref().as_table()) {
+            for (_, table) in defaults {
+                if let Some(table) = table.get_ref().as_table() {
+                    used.extend(table.keys().map(|k| k.get_ref().to_string()));
+                }
+            }
+        }
+
+        Some(used)
+    }
+}
+
+/// A preferred unit must be a unit, and fit the property's dimension when it has one
+#[cfg(not(target_arch = "wasm32"))]
+fn check_preferred_unit(
+    check: &mut Check,
+    parser: &mut DimParser,
+    property: &str,
+    unit: &Spanned<DeValue>,
+    unit_str: &str,
+    dimension: Option<&str>,
+) {
+    let parsed = match parser.parse_unit_string(&format!("1 {}", unit_str)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            check.error(
+                "invalid-unit",
+                format!(
+                    "Preferred unit '{}' for '{}' is not a recognised unit: {}",
+                    unit_str, property, e
+                ),
+                unit,
+            );
+            return;
+        }
+    };
+    if let Some(dimension) = dimension {
+        if let Err(e) = validate_dimension(parser, &parsed, dimension) {
+            check.error(
+                "unit-dimension-mismatch",
+                format!(
+                    "Preferred unit '{}' for '{}' doesn't fit dimension '{}': {}",
+                    unit_str, property, dimension, e
+                ),
+                unit,
+            );
+        }
+    }
+}
+
+impl Default for ConfigValidator<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accumulates issues, turning byte spans into "file:line:column" locations
+struct Check<'c> {
+    content: &'c str,
+    file: &'c str,
+    result: ValidationResult,
+}
+
+impl Check<'_> {
+    fn location(&self, span: &Range<usize>) -> String {
+        let before = &self.content[..span.start.min(self.content.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let column = before[line_start..].chars().count() + 1;
+        format!("{}:{}:{}", self.file, line, column)
+    }
+
+    fn error<T>(&mut self, code: &'static str, message: String, at: &Spanned<T>) {
+        let location = self.location(&at.span());
+        self.result.add_error(code, message, Some(location));
+    }
+
+    fn warning<T>(&mut self, code: &'static str, message: String, at: &Spanned<T>) {
+        let location = self.location(&at.span());
+        self.result.add_warning(code, message, Some(location));
+    }
+}
+
+/// A scope chain must be a non-empty list of known scope names
+fn check_chain(check: &mut Check, chain: &Spanned<DeValue>, context: &str) {
+    let Some(scopes) = chain.get_ref().as_array() else {
+        check.error(
+            "invalid-chain",
+            format!("{} must be a list of scopes", context),
+            chain,
+        );
+        return;
+    };
+
+    if scopes.is_empty() {
+        check.warning(
+            "empty-chain",
+            format!(
+                "Empty inheritance chain in {}; the property never resolves",
+                context
+            ),
+            chain,
+        );
+    }
+
+    for scope in scopes.iter() {
+        let name = scope.get_ref().as_str();
+        if !name.is_some_and(|n| ScopeLevel::ALL.iter().any(|s| s.as_str() == n)) {
+            check.error(
+                "unknown-scope",
+                format!(
+                    "Unknown scope level {} in {} (expected one of: {})",
+                    name.map(|n| format!("'{}'", n))
+                        .unwrap_or_else(|| scope.get_ref().type_str().to_string()),
+                    context,
+                    ScopeLevel::ALL
+                        .iter()
+                        .map(|s| s.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                scope,
+            );
+        }
+    }
+}
+
+fn check_overrides(
+    check: &mut Check,
+    overrides: &Spanned<DeValue>,
+    property: &str,
+    known_block_types: Option<&HashSet<String>>,
+) {
+    let Some(overrides) = overrides.get_ref().as_table() else {
+        check.error(
+            "invalid-rule",
+            format!(
+                "Overrides for '{}' must be a table of block types",
+                property
+            ),
+            overrides,
+        );
+        return;
+    };
+
+    for (block_type, chain) in overrides {
+        check_chain(
+            check,
+            chain,
+            &format!("override for '{}' on {}", property, block_type.get_ref()),
+        );
+        check_block_type(
+            check,
+            block_type,
+            known_block_types,
+            &format!("overrides for '{}'", property),
+        );
+    }
+}
+
+fn check_block_type<T: AsRef<str>>(
+    check: &mut Check,
+    block_type: &Spanned<T>,
+    known_block_types: Option<&HashSet<String>>,
+    context: &str,
+) {
+    if let Some(known) = known_block_types {
+        if !known.contains(block_type.get_ref().as_ref()) {
+            check.warning(
+                "unknown-block-type",
+                format!(
+                    "Block type '{}' in {} is not defined by any schema",
+                    block_type.get_ref().as_ref(),
+                    context
+                ),
+                block_type,
+            );
+        }
+    }
+}
+
+fn check_choice(check: &mut Check, value: &Spanned<DeValue>, key: &str, choices: &[&str]) {
+    if !value
+        .get_ref()
+        .as_str()
+        .is_some_and(|v| choices.contains(&v))
+    {
+        check.error(
+            "invalid-value",
+            format!("{} must be one of: {}", key, choices.join(", ")),
+            value,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn network_with_block_property(property: &str) -> Network {
+        let mut extra = HashMap::new();
+        extra.insert(property.to_string(), toml::Value::Float(1.0));
+        Network {
+            id: "test".to_string(),
+            label: "Test".to_string(),
+            nodes: vec![NodeData::Branch(BranchNode {
+                base: NodeBase {
+                    id: "branch-1".to_string(),
+                    type_: "branch".to_string(),
+                    label: None,
+                    position: Position { x: 0.0, y: 0.0 },
+                    parent_id: None,
+                    width: None,
+                    height: None,
+                    extra: HashMap::new(),
+                },
+                outgoing: vec![],
+                blocks: vec![Block {
+                    quantity: Some(1),
+                    type_: "Pipe".to_string(),
+                    extra,
+                }],
+            })],
+            edges: vec![],
+        }
+    }
+
+    #[test]
+    fn test_reports_unknown_scope_with_line_and_column() {
+        let content = r#"[inheritance]
+general = ["block", "grup", "global"]
+"#;
+        let result = ConfigValidator::new().validate(content, "config.toml");
+
+        assert_eq!(result.errors.len(), 1);
+        let issue = &result.errors[0];
+        assert_eq!(issue.code, "unknown-scope");
+        assert!(issue.message.contains("'grup'"));
+        assert_eq!(issue.location.as_deref(), Some("config.toml:2:21"));
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let content = "[inheritance]\ngeneral = [\"blöck\", \"grup\"] # °C\n";
+        let result = ConfigValidator::new().validate(content, "config.toml");
+
+        let locations: Vec<_> = result
+            .errors
+            .iter()
+            .map(|e| e.location.as_deref().unwrap_or(""))
+            .collect();
+        assert_eq!(locations, vec!["config.toml:2:12", "config.toml:2:21"]);
+    }
+
+    #[test]
+    fn test_reports_malformed_empty_and_unused_rules() {
+        let content = r#"[inheritance.rules]
+roughness = []
+lenght = ["block", "global"]
+pressure = "block"
+"#;
+        let network = network_with_block_property("roughness");
+        let result = ConfigValidator::new()
+            .with_network(&network)
+            .validate(content, "config.toml");
+
+        let codes: Vec<&str> = result.issues().map(|i| i.code).collect();
+        assert!(codes.contains(&"invalid-rule"));
+        assert!(codes.contains(&"empty-chain"));
+
+        let unused: Vec<_> = result
+            .warnings
+            .iter()
+            .filter(|w| w.code == "unused-rule")
+            .collect();
+        assert_eq!(unused.len(), 2);
+        assert!(unused
+            .iter()
+            .any(|w| w.location.as_deref() == Some("config.toml:3:1")));
+    }
+
+    #[test]
+    fn test_parse_errors_are_located() {
+        let result = ConfigValidator::new().validate("[inheritance\n", "config.toml");
+        assert_eq!(result.errors[0].code, "config-parse");
+        assert!(result.errors[0]
+            .location
+            .as_deref()
+            .is_some_and(|l| l.starts_with("config.toml:1:")));
+    }
+}
@@ -103,6 +103,88 @@ impl DaggerWasm {
         Ok(json)
     }
 
+    /// Load a network with every inherited property materialized into each block
+    /// Each materialized property is tagged with its source scope under "_<property>_scope"
+    /// With a schema library, every property the block type's schema declares is resolved too
+    /// Returns JSON string of the network
+    #[wasm_bindgen]
+    pub fn materialize_network_from_files(
+        &self,
+        files_json: &str,
+        config_content: Option<String>,
+        schemas_dir: Option<String>,
+        version: Option<String>,
+    ) -> Result<String, JsValue> {
+        // Parse the JSON string into a HashMap
+        let files: std::collections::HashMap<String, String> = serde_json::from_str(files_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse files JSON: {}", e)))?;
+
+        // Load network
+        let (network, _validation) = parser::load_network_from_files(files, config_content.clone())
+            .map_err(|e| JsValue::from_str(&format!("Failed to load network: {}", e)))?;
+
+        // Load config for scope resolution
+        let config = if let Some(config_content) = config_content {
+            scope::config::Config::load_from_str(&config_content)
+                .map_err(|e| JsValue::from_str(&format!("Failed to load config: {}", e)))?
+        } else {
+            scope::config::Config::empty()
+        };
+        let resolver = scope::resolver::ScopeResolver::new(config);
+
+        let materialized = if let (Some(schemas_dir), Some(version)) = (schemas_dir, version) {
+            let mut schemas =
+                schema::registry::SchemaRegistry::new(std::path::PathBuf::from(schemas_dir));
+            schemas
+                .load_library(&version)
+                .map_err(|e| JsValue::from_str(&format!("Failed to load schema library: {}", e)))?;
+            network.materialize_with_schema(&resolver, &schemas, &version)
+        } else {
+            network.materialize(&resolver)
+        }
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        let json = serde_json::to_string(&materialized)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize network: {}", e)))?;
+
+        Ok(json)
+    }
+
+    /// Load a network with the schema `default` of every property a block neither
+    /// sets nor inherits filled in, tagged "_<property>_scope" = "default"
+    /// Returns JSON string of the network
+    #[wasm_bindgen]
+    pub fn load_network_with_defaults_from_files(
+        &self,
+        files_json: &str,
+        config_content: Option<String>,
+        schemas_dir: &str,
+        version: &str,
+    ) -> Result<String, JsValue> {
+        // Parse the JSON string into a HashMap
+        let files: std::collections::HashMap<String, String> = serde_json::from_str(files_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse files JSON: {}", e)))?;
+
+        let mut schemas =
+            schema::registry::SchemaRegistry::new(std::path::PathBuf::from(schemas_dir));
+        schemas
+            .load_library(version)
+            .map_err(|e| JsValue::from_str(&format!("Failed to load schema library: {}", e)))?;
+
+        let (network, _validation) = parser::load_network_from_files_with_schema_defaults(
+            files,
+            config_content,
+            &schemas,
+            version,
+        )
+        .map_err(|e| JsValue::from_str(&format!("Failed to load network: {}", e)))?;
+
+        let json = serde_json::to_string(&network)
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize network: {}", e)))?;
+
+        Ok(json)
+    }
+
     /// Get all nodes in the network
     /// Returns JSON string array of nodes
     #[wasm_bindgen]
@@ -180,38 +262,30 @@ impl DaggerWasm {
             })
             .ok_or_else(|| JsValue::from_str(&format!("Node '{}' not found", node_id)))?;
 
-        // Get the block
-        let block = branch_node.blocks.get(block_index).ok_or_else(|| {
-            JsValue::from_str(&format!(
+        // Check the block exists
+        if block_index >= branch_node.blocks.len() {
+            return Err(JsValue::from_str(&format!(
                 "Block index {} out of range ({} blocks)",
                 block_index,
                 branch_node.blocks.len()
-            ))
-        })?;
+            )));
+        }
 
-        // Find the group if parent_id exists
-        let group = branch_node.base.parent_id.as_ref().and_then(|parent_id| {
-            network.nodes.iter().find_map(|n| match n {
-                parser::models::NodeData::Group(g) if g.base.id == *parent_id => Some(g),
-                _ => None,
-            })
-        });
+        // Block with its enclosing groups and upstream network
+        let ctx = scope::resolver::BlockContext::in_network(&network, branch_node, block_index)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
         // Resolve the property with scope
-        let result = resolver.resolve_property_with_scope(property, block, branch_node, group);
+        let result = resolver
+            .resolve_property_with_scope(property, &ctx)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
         match result {
             Some((value, scope_level)) => {
                 // Convert TOML Value to JSON Value
                 let json_value = query::executor::toml_to_json(&value);
 
-                // Convert scope level to string
-                let scope_str = match scope_level {
-                    scope::config::ScopeLevel::Block => "block",
-                    scope::config::ScopeLevel::Branch => "branch",
-                    scope::config::ScopeLevel::Group => "group",
-                    scope::config::ScopeLevel::Global => "global",
-                };
+                let scope_str = scope_level.as_str();
 
                 // Return both value and scope
                 let result_obj = serde_json::json!({
@@ -319,6 +393,8 @@ impl DaggerWasm {
                     serde_json::json!({
                         "block_type": schema.block_type,
                         "version": schema.version,
+                        "description": schema.description,
+                        "extends": schema.extends,
                         "required_properties": schema.required_properties,
                         "optional_properties": schema.optional_properties,
                     }),
@@ -332,6 +408,50 @@ impl DaggerWasm {
         Ok(json)
     }
 
+    /// Catalog of known properties for property pickers
+    /// Merges config.toml, the network's blocks and (optionally) a schema library
+    /// Returns JSON array of { name, description?, dimension?, defaultUnit?, default?,
+    /// scopes, rule, overrides?, blockTypes, requiredBy? }, sorted by name
+    #[wasm_bindgen]
+    pub fn get_property_catalog(
+        &self,
+        files_json: &str,
+        config_content: Option<String>,
+        schemas_dir: Option<String>,
+        version: Option<String>,
+    ) -> Result<String, JsValue> {
+        // Parse the JSON string into a HashMap
+        let files: std::collections::HashMap<String, String> = serde_json::from_str(files_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse files JSON: {}", e)))?;
+
+        // Load network
+        let (network, _validation) = parser::load_network_from_files(files, config_content.clone())
+            .map_err(|e| JsValue::from_str(&format!("Failed to load network: {}", e)))?;
+
+        let config = if let Some(config_content) = config_content {
+            scope::config::Config::load_from_str(&config_content)
+                .map_err(|e| JsValue::from_str(&format!("Failed to load config: {}", e)))?
+        } else {
+            scope::config::Config::empty()
+        };
+
+        let mut registry = scope::registry::PropertyRegistry::new(config);
+        if let (Some(schemas_dir), Some(version)) = (schemas_dir, version) {
+            let mut schemas =
+                schema::registry::SchemaRegistry::new(std::path::PathBuf::from(schemas_dir));
+            schemas
+                .load_library(&version)
+                .map_err(|e| JsValue::from_str(&format!("Failed to load schema library: {}", e)))?;
+            registry = registry.with_schemas(&schemas, &version);
+        }
+        let registry = registry.with_network(&network);
+
+        let json = serde_json::to_string(&registry.list())
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize catalog: {}", e)))?;
+
+        Ok(json)
+    }
+
     /// Resolve properties for multiple blocks in a single call.
     /// This parses the network once and resolves all requested properties,
     /// avoiding repeated parsing overhead.
@@ -362,21 +482,13 @@ impl DaggerWasm {
         };
         let resolver = scope::resolver::ScopeResolver::new(config);
 
-        // Build lookup maps for branches and groups
+        // Build lookup map for branches
         let mut branch_map: std::collections::HashMap<&str, &parser::models::BranchNode> =
             std::collections::HashMap::new();
-        let mut group_map: std::collections::HashMap<&str, &parser::models::GroupNode> =
-            std::collections::HashMap::new();
 
         for node in &network.nodes {
-            match node {
-                parser::models::NodeData::Branch(b) => {
-                    branch_map.insert(&b.base.id, b);
-                }
-                parser::models::NodeData::Group(g) => {
-                    group_map.insert(&g.base.id, g);
-                }
-                _ => {}
+            if let parser::models::NodeData::Branch(b) = node {
+                branch_map.insert(&b.base.id, b);
             }
         }
 
@@ -405,32 +517,25 @@ impl DaggerWasm {
                 None => continue,
             };
 
-            let block = match branch.blocks.get(request.block_index) {
-                Some(b) => b,
-                None => continue,
-            };
+            if request.block_index >= branch.blocks.len() {
+                continue;
+            }
 
-            // Find parent group
-            let group = branch
-                .base
-                .parent_id
-                .as_ref()
-                .and_then(|pid| group_map.get(pid.as_str()).copied());
+            // Block with its enclosing groups and upstream network
+            let ctx =
+                scope::resolver::BlockContext::in_network(&network, branch, request.block_index)
+                    .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
             let mut block_results: std::collections::HashMap<String, serde_json::Value> =
                 std::collections::HashMap::new();
 
             for property in &request.properties {
-                if let Some((value, scope_level)) =
-                    resolver.resolve_property_with_scope(property, block, branch, group)
+                if let Some((value, scope_level)) = resolver
+                    .resolve_property_with_scope(property, &ctx)
+                    .map_err(|e| JsValue::from_str(&e.to_string()))?
                 {
                     let json_value = query::executor::toml_to_json(&value);
-                    let scope_str = match scope_level {
-                        scope::config::ScopeLevel::Block => "block",
-                        scope::config::ScopeLevel::Branch => "branch",
-                        scope::config::ScopeLevel::Group => "group",
-                        scope::config::ScopeLevel::Global => "global",
-                    };
+                    let scope_str = scope_level.as_str();
                     block_results.insert(
                         property.clone(),
                         serde_json::json!({
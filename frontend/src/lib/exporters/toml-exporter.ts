@@ -63,11 +63,18 @@ function serializeNodeToToml(
         // type is required
         blockObj.type = block.type;
         // Add any extra properties from the block (excluding computed fields)
+        const extra = block as Record<string, unknown>;
         Object.keys(block).forEach((key) => {
           // Skip known fields and computed fields (kind, label are computed by backend)
-          if (!["type", "quantity", "kind", "label"].includes(key)) {
-            blockObj[key] = (block as Record<string, unknown>)[key];
+          if (["type", "quantity", "kind", "label"].includes(key)) {
+            return;
           }
+          // Skip values filled in from schema defaults, with their metadata
+          const property = key.match(/^_(.+)_(?:scope|original)$/)?.[1] ?? key;
+          if (extra[`_${property}_scope`] === "default") {
+            return;
+          }
+          blockObj[key] = extra[key];
         });
         return blockObj;
       });